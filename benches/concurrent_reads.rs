@@ -0,0 +1,88 @@
+// Measures the throughput of concurrent get_document calls against a
+// local stub server, which answers every request after a fixed latency.
+// Run with `cargo bench --bench concurrent_reads`
+use database::{
+    database::Database,
+    firestore::Firestore,
+};
+use futures::executor::block_on;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// simulated network and server latency of each request
+const LATENCY: Duration = Duration::from_millis(10);
+const READS_PER_READER: usize = 50;
+const READERS: &[usize] = &[1, 2, 4, 8, 16, 32];
+
+const DOCUMENT: &str = r#"{
+  "name": "projects/bench/databases/(default)/documents/bench/document",
+  "fields": { "count": { "integerValue": "1" } },
+  "createTime": "2020-01-01T00:00:00.000000Z",
+  "updateTime": "2020-01-01T00:00:00.000000Z"
+}"#;
+
+// answers requests on a keep-alive connection until it is closed
+fn serve(stream: TcpStream) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut stream = stream;
+    loop {
+        // GET requests have no body, so the request ends with an empty line
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match reader.read_line(&mut line) {
+                Ok(0) | Err(_) => return,
+                Ok(_) if line == "\r\n" => break,
+                Ok(_) => {},
+            }
+        }
+        thread::sleep(LATENCY);
+        let response = format!("HTTP/1.1 200 OK\r\n\
+                                Content-Type: application/json\r\n\
+                                Content-Length: {}\r\n\r\n{}",
+                               DOCUMENT.len(),
+                               DOCUMENT);
+        if stream.write_all(response.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+fn stub_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            thread::spawn(move || serve(stream));
+        }
+    });
+    address
+}
+
+fn main() {
+    let firestore = Firestore::with_emulator("bench", &stub_server());
+    println!("{:>8} {:>12} {:>12}", "readers", "seconds", "reads/s");
+    for &readers in READERS {
+        let start = Instant::now();
+        let threads: Vec<_> = (0..readers)
+            .map(|_| {
+                let firestore = firestore.clone();
+                thread::spawn(move || {
+                    for _ in 0..READS_PER_READER {
+                        block_on(firestore.get_document("bench", "document")).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let seconds = start.elapsed().as_secs_f64();
+        println!("{:>8} {:>12.3} {:>12.1}",
+                 readers,
+                 seconds,
+                 (readers * READS_PER_READER) as f64 / seconds);
+    }
+}
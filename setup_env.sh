@@ -2,3 +2,8 @@
 
 DIR="$( cd "$( dirname "${BASH_SOURCE[0]}" )" >/dev/null 2>&1 && pwd )"
 export GOOGLE_APPLICATION_CREDENTIALS="$DIR/test-project-service-key.json"
+# to run against a local emulator instead, e.g.:
+# export FIRESTORE_EMULATOR_HOST="localhost:8080"
+# or to use a token from `gcloud auth print-access-token`:
+# export GOOGLE_OAUTH_ACCESS_TOKEN="$(gcloud auth print-access-token)"
+# export GOOGLE_CLOUD_PROJECT="test-project"
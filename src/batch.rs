@@ -0,0 +1,153 @@
+use crate::{
+    database::{Database, DatabaseFuture},
+    document::{
+        Document,
+        FieldPath,
+    },
+    write::{
+        Write,
+        FieldTransform,
+        Precondition,
+        SetOptions,
+    },
+};
+
+// WriteBatch collects writes to any collections
+// of a database and commits them atomically
+pub struct WriteBatch<'a, DB: Database<'a>> {
+    database: &'a DB,
+    writes: Vec<Write>,
+}
+
+impl<'a, DB: Database<'a>> WriteBatch<'a, DB> {
+    pub fn new(database: &'a DB) -> Self {
+        Self {
+            database,
+            writes: Vec::new(),
+        }
+    }
+    pub fn writes(&self) -> &[Write] {
+        &self.writes
+    }
+    pub fn write(mut self, write: Write) -> Self {
+        self.writes.push(write);
+        self
+    }
+    pub fn create<T: ToString>(
+        self,
+        collection_id: T,
+        document: Document,
+        ) -> Self {
+        let document = self.database.with_id(document);
+        self.write(Write::Create {
+            collection_id: collection_id.to_string(),
+            document,
+        })
+    }
+    pub fn update<A: ToString, B: ToString>(
+        self,
+        collection_id: A,
+        document_id: B,
+        document: Document,
+        mask: Option<Vec<FieldPath>>,
+        ) -> Self {
+        self.write(Write::Update {
+            collection_id: collection_id.to_string(),
+            document_id: document_id.to_string(),
+            document,
+            mask,
+            transforms: Vec::new(),
+            precondition: Precondition::default(),
+        })
+    }
+    // update a document and transform its fields in the same write
+    pub fn update_with_transforms<A: ToString, B: ToString>(
+        self,
+        collection_id: A,
+        document_id: B,
+        document: Document,
+        mask: Option<Vec<FieldPath>>,
+        transforms: Vec<FieldTransform>,
+        ) -> Self {
+        self.write(Write::Update {
+            collection_id: collection_id.to_string(),
+            document_id: document_id.to_string(),
+            document,
+            mask,
+            transforms,
+            precondition: Precondition::default(),
+        })
+    }
+    // create or change a document, whether it exists or not
+    pub fn set<T: ToString>(
+        self,
+        collection_id: T,
+        document: Document,
+        options: SetOptions,
+        ) -> Self {
+        let document = self.database.with_id(document);
+        self.write(Write::Set {
+            collection_id: collection_id.to_string(),
+            document,
+            options,
+        })
+    }
+    // update a document if the precondition holds
+    pub fn update_if<A: ToString, B: ToString>(
+        self,
+        collection_id: A,
+        document_id: B,
+        document: Document,
+        mask: Option<Vec<FieldPath>>,
+        precondition: Precondition,
+        ) -> Self {
+        self.write(Write::Update {
+            collection_id: collection_id.to_string(),
+            document_id: document_id.to_string(),
+            document,
+            mask,
+            transforms: Vec::new(),
+            precondition,
+        })
+    }
+    pub fn delete<A: ToString, B: ToString>(
+        self,
+        collection_id: A,
+        document_id: B,
+        ) -> Self {
+        self.write(Write::Delete {
+            collection_id: collection_id.to_string(),
+            document_id: document_id.to_string(),
+            precondition: None,
+        })
+    }
+    // delete a document if the precondition holds
+    pub fn delete_if<A: ToString, B: ToString>(
+        self,
+        collection_id: A,
+        document_id: B,
+        precondition: Precondition,
+        ) -> Self {
+        self.write(Write::Delete {
+            collection_id: collection_id.to_string(),
+            document_id: document_id.to_string(),
+            precondition: Some(precondition),
+        })
+    }
+    pub fn transform<A: ToString, B: ToString>(
+        self,
+        collection_id: A,
+        document_id: B,
+        transforms: Vec<FieldTransform>,
+        ) -> Self {
+        self.write(Write::Transform {
+            collection_id: collection_id.to_string(),
+            document_id: document_id.to_string(),
+            transforms,
+        })
+    }
+    // commit all writes, either all or none of them are applied
+    pub fn commit(self) -> DatabaseFuture<()> {
+        self.database.commit(self.writes)
+    }
+}
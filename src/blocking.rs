@@ -0,0 +1,118 @@
+use crate::{
+    database::DatabaseFuture,
+    error::{
+        DatabaseError,
+        DatabaseErrorKind,
+    },
+};
+use lazy_static::lazy_static;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+
+use futures::{
+    channel::oneshot,
+    future::FutureExt,
+};
+
+// the number of threads running blocking calls. Calls made while
+// all threads are busy wait in a queue until a thread is free,
+// so at most this many blocking requests are in flight at once
+pub const BLOCKING_THREADS: usize = 32;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+// a fixed number of threads taking jobs from a shared queue
+struct BlockingPool {
+    // Senders can not be shared between threads
+    sender: Mutex<mpsc::Sender<Job>>,
+}
+
+impl BlockingPool {
+    fn new(threads: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for i in 0..threads {
+            let receiver = receiver.clone();
+            thread::Builder::new()
+                .name(format!("database-blocking-{}", i))
+                .spawn(move || loop {
+                    // the lock is only held to take a job, not to run it
+                    let job = receiver.lock().unwrap().recv();
+                    match job {
+                        // a panicking job drops its sender, which
+                        // cancels its future, and the thread lives on
+                        Ok(job) => { let _ = panic::catch_unwind(AssertUnwindSafe(job)); },
+                        Err(_) => break,
+                    }
+                })
+                .expect("Failed to spawn a blocking thread");
+        }
+        Self {
+            sender: Mutex::new(sender),
+        }
+    }
+    fn spawn(&self, job: Job) {
+        // the threads never stop, so the queue is never closed
+        let _ = self.sender.lock().unwrap().send(job);
+    }
+}
+
+lazy_static! {
+    static ref POOL: BlockingPool = BlockingPool::new(BLOCKING_THREADS);
+}
+
+// runs a blocking call on one of BLOCKING_THREADS threads,
+// so the returned future can be awaited on any executor.
+// f must not wait for other blocking calls, which could
+// wait for a free thread forever when all threads do so
+pub(crate) fn blocking<T, F>(f: F) -> DatabaseFuture<T>
+    where T: Send + 'static,
+          F: FnOnce() -> Result<T, DatabaseError> + Send + 'static
+{
+    let (sender, receiver) = oneshot::channel();
+    POOL.spawn(Box::new(move || {
+        // the future may have been dropped already
+        let _ = sender.send(f());
+    }));
+    Box::pin(receiver.map(|result| result.unwrap_or_else(|_| Err(DatabaseError::new(
+                    DatabaseErrorKind::Cancelled,
+                    "The blocking operation was cancelled")))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{
+        executor::block_on,
+        future::join_all,
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn bounded() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let calls = (0..BLOCKING_THREADS * 3)
+            .map(|_| {
+                let (running, most) = (running.clone(), most.clone());
+                blocking(move || {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    most.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(5));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    Ok(())
+                })
+            })
+            .collect::<Vec<_>>();
+        assert!(block_on(join_all(calls)).into_iter().all(|r| r.is_ok()));
+        assert!(most.load(Ordering::SeqCst) <= BLOCKING_THREADS);
+    }
+    #[test]
+    fn panic_cancels() {
+        let result = block_on(blocking(|| -> Result<(), DatabaseError> { panic!("test") }));
+        assert!(result.is_err());
+        block_on(blocking(|| Ok(()))).unwrap();
+    }
+}
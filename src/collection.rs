@@ -1,11 +1,8 @@
-use crate::error::*;
 use crate::{
-    database::Database,
-    document::{Document},
-};
-
-use futures::future::{
-    Future,
+    database::{Database, DatabaseFuture, DocumentStream, Lookup},
+    document::{Document, FieldPath},
+    list::{ListOptions},
+    write::{FieldTransform, Precondition, SetOptions},
 };
 
 pub trait Collection<'db> : Sized {
@@ -17,18 +14,61 @@ pub trait Collection<'db> : Sized {
     fn get_document<T: ToString>(
         &self,
         document_id: T,
-    ) -> Box<dyn Future<Item=Document, Error=DatabaseError> + Send>;
+    ) -> DatabaseFuture<Document>;
+    fn get_document_masked<T: ToString>(
+        &self,
+        document_id: T,
+        mask: Option<Vec<FieldPath>>,
+    ) -> DatabaseFuture<Document>;
+
+    // reads many documents of the collection at once
+    fn get_documents_by_id<T: ToString>(
+        &self,
+        document_ids: &[T],
+    ) -> DatabaseFuture<Vec<Lookup>>;
 
     fn create_document(
         &self,
         document: Document,
-    ) -> Box<dyn Future<Item=String, Error=DatabaseError> + Send>;
+    ) -> DatabaseFuture<String>;
+    fn set_document(
+        &self,
+        document: Document,
+        options: SetOptions,
+    ) -> DatabaseFuture<()>;
+    fn update_document<T: ToString>(
+        &self,
+        document_id: T,
+        document: Document,
+        mask: Option<Vec<FieldPath>>,
+    ) -> DatabaseFuture<Document>;
+    fn update_document_if<T: ToString>(
+        &self,
+        document_id: T,
+        document: Document,
+        mask: Option<Vec<FieldPath>>,
+        precondition: Precondition,
+    ) -> DatabaseFuture<Document>;
     fn delete_document<T: ToString>(
         &self,
         document_id: T,
-    ) -> Box<dyn Future<Item=(), Error=DatabaseError> + Send>;
+    ) -> DatabaseFuture<()>;
+    fn delete_document_if<T: ToString>(
+        &self,
+        document_id: T,
+        precondition: Option<Precondition>,
+    ) -> DatabaseFuture<()>;
+    fn transform_document<T: ToString>(
+        &self,
+        document_id: T,
+        transforms: Vec<FieldTransform>,
+    ) -> DatabaseFuture<()>;
 
+    fn list_documents(
+        &self,
+        options: ListOptions,
+    ) -> DocumentStream;
     fn get_documents(
         &self
-    ) -> Box<dyn Future<Item=Vec<Document>, Error=DatabaseError>>;
+    ) -> DatabaseFuture<Vec<Document>>;
 }
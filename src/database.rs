@@ -1,16 +1,47 @@
 use crate::error::*;
-use crate::document::{Document};
+use crate::document::{Document, FieldPath};
 use crate::collection::{Collection};
+use crate::write::{Write, FieldTransform, Precondition, SetOptions};
+use crate::batch::{WriteBatch};
+use crate::transaction::{Transaction};
 
-use futures::future::{
-    Future,
+use crate::list::{ListOptions};
+use crate::id;
+
+use futures::{
+    future::BoxFuture,
+    stream::{BoxStream, TryStreamExt},
 };
 
+// the result of an operation which completes asynchronously.
+// It can be awaited on any executor
+pub type DatabaseFuture<T> = BoxFuture<'static, Result<T, DatabaseError>>;
+// documents which are read while the stream is consumed
+pub type DocumentStream = BoxStream<'static, Result<Document, DatabaseError>>;
+
+// the result of reading one of many documents at once
+#[derive(Clone, Debug, PartialEq)]
+pub enum Lookup {
+    Found(Document),
+    // the full path of a document which does not exist
+    Missing(String),
+}
+
+impl Lookup {
+    pub fn found(self) -> Option<Document> {
+        match self {
+            Lookup::Found(document) => Some(document),
+            Lookup::Missing(_) => None,
+        }
+    }
+}
+
 pub trait Database<'a>
 {
     type Access;
     type Collection: Collection<'a>;
     type Query;
+    type Transaction: Transaction<'a, Database=Self>;
 
     fn new(access: Self::Access) -> Self;
     fn get_path(&'a self) -> String;
@@ -25,23 +56,155 @@ pub trait Database<'a>
         format!("{}/{}", self.get_path(), collection_id.to_string())
     }
 
+    // creates a document and returns its full path. Documents
+    // without a name are named with a generated id
     fn create_document<T: ToString>(
         &'a self,
         collection_id: T,
         document: Document
-        ) -> Box<dyn Future<Item=String, Error=DatabaseError> + Send>;
+        ) -> DatabaseFuture<String>;
+    // a new id for a document, see id::IdGenerator
+    fn generate_id(&'a self) -> String;
+    // the document, named with a generated id if it has no name
+    fn with_id(&'a self, document: Document) -> Document {
+        id::named_or(document, || self.generate_id())
+    }
     fn get_document<A: ToString, B: ToString>(
         &'a self,
         collection_id: A,
         document_id: B,
-        ) -> Box<dyn Future<Item=Document, Error=DatabaseError> + Send>;
+        ) -> DatabaseFuture<Document> {
+        self.get_document_masked(collection_id, document_id, None)
+    }
+    // reads only the masked fields of a document. The returned
+    // document has the mask as projection
+    fn get_document_masked<A: ToString, B: ToString>(
+        &'a self,
+        collection_id: A,
+        document_id: B,
+        mask: Option<Vec<FieldPath>>,
+        ) -> DatabaseFuture<Document>;
+    // reads many documents at once, given by their paths relative to
+    // the database root (e.g. "users/alice"). Returns a lookup for
+    // each path in the same order
+    fn get_documents_by_id<T: ToString>(
+        &'a self,
+        paths: &[T],
+        ) -> DatabaseFuture<Vec<Lookup>>;
+    // updates the fields of an existing document. With a mask only
+    // the masked fields are changed, masked fields missing in the
+    // given document are deleted. Without a mask all fields are replaced
+    fn update_document<A: ToString, B: ToString>(
+        &'a self,
+        collection_id: A,
+        document_id: B,
+        document: Document,
+        mask: Option<Vec<FieldPath>>,
+        ) -> DatabaseFuture<Document> {
+        self.update_document_if(collection_id, document_id, document, mask, Precondition::default())
+    }
+    // creates or changes a document, whether it exists or not,
+    // so writing the same document again has no further effect
+    fn set_document<T: ToString>(
+        &'a self,
+        collection_id: T,
+        document: Document,
+        options: SetOptions,
+        ) -> DatabaseFuture<()> {
+        self.commit(vec![Write::Set {
+            collection_id: collection_id.to_string(),
+            document: self.with_id(document),
+            options,
+        }])
+    }
+    // updates a document if the precondition holds, e.g. only if it
+    // was not changed since it was read with Precondition::unchanged.
+    // With Precondition::Exists(false) the document is created
+    fn update_document_if<A: ToString, B: ToString>(
+        &'a self,
+        collection_id: A,
+        document_id: B,
+        document: Document,
+        mask: Option<Vec<FieldPath>>,
+        precondition: Precondition,
+        ) -> DatabaseFuture<Document>;
+    // deleting a document which does not exist is not an error
     fn delete_document<A: ToString, B: ToString>(
         &'a self,
         collection_id: A,
         document_id: B,
-        ) -> Box<dyn Future<Item=(), Error=DatabaseError> + Send>;
+        ) -> DatabaseFuture<()> {
+        self.delete_document_if(collection_id, document_id, None)
+    }
+    fn delete_document_if<A: ToString, B: ToString>(
+        &'a self,
+        collection_id: A,
+        document_id: B,
+        precondition: Option<Precondition>,
+        ) -> DatabaseFuture<()>;
+    // changes fields based on their current values without
+    // reading them, creating the document if it does not exist
+    fn transform_document<A: ToString, B: ToString>(
+        &'a self,
+        collection_id: A,
+        document_id: B,
+        transforms: Vec<FieldTransform>,
+        ) -> DatabaseFuture<()> {
+        self.commit(vec![Write::Transform {
+            collection_id: collection_id.to_string(),
+            document_id: document_id.to_string(),
+            transforms,
+        }])
+    }
+    // lists the documents of a collection,
+    // requesting further pages as they are needed
+    fn list_documents<A: ToString>(
+        &'a self,
+        collection_id: A,
+        options: ListOptions,
+        ) -> DocumentStream;
+    // all documents of a collection
     fn get_documents<A: ToString>(
         &'a self,
         collection_id: A,
-        ) -> Box<dyn Future<Item=Vec<Document>, Error=DatabaseError>>;
+        ) -> DatabaseFuture<Vec<Document>> {
+        Box::pin(self.list_documents(collection_id, ListOptions::default())
+                     .try_collect())
+    }
+
+    // start a batch of writes to commit atomically
+    fn batch(&'a self) -> WriteBatch<'a, Self>
+        where Self: Sized
+    {
+        WriteBatch::new(self)
+    }
+    // apply all writes atomically
+    fn commit(
+        &'a self,
+        writes: Vec<Write>,
+        ) -> DatabaseFuture<()>;
+
+    // blocking transaction primitives used by run_transaction
+    fn begin_transaction(&'a self) -> Result<Self::Transaction, DatabaseError>;
+    fn commit_transaction(
+        &'a self,
+        transaction: Self::Transaction,
+        ) -> Result<(), DatabaseError>;
+    fn rollback_transaction(
+        &'a self,
+        transaction: Self::Transaction,
+        ) -> Result<(), DatabaseError>;
+    // runs f in a transaction and commits its writes. f is called
+    // again in a new transaction when the commit is aborted because
+    // of contention, so it should not have other side effects.
+    // The reads in f block, so f is run on a blocking thread. These
+    // threads are shared by all calls, so f must not wait for other
+    // calls on the database (e.g. with block_on). Once all threads
+    // run such transactions, the calls never get a thread
+    fn run_transaction<T, F>(
+        &'a self,
+        f: F,
+        ) -> DatabaseFuture<T>
+        where T: Send + 'static,
+              F: FnMut(&mut Self::Transaction) -> Result<T, DatabaseError> + Send + 'static;
 }
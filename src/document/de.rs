@@ -0,0 +1,350 @@
+use super::{
+    FieldValue,
+    ser::SerdeError,
+};
+use serde::de::{
+    self,
+    Visitor,
+    IntoDeserializer,
+    DeserializeOwned,
+    value::{
+        SeqDeserializer,
+        MapDeserializer,
+    },
+};
+
+// converts a FieldValue to any deserializable value,
+// the inverse of ser::to_value
+pub fn from_value<T: DeserializeOwned>(value: FieldValue) -> Result<T, SerdeError> {
+    T::deserialize(value)
+}
+
+fn is_null(v: &google_firestore::Value) -> bool {
+    // values read from Firestore have no field set for null
+    v.null_value.is_some() || (
+        v.boolean_value.is_none() &&
+        v.integer_value.is_none() &&
+        v.double_value.is_none() &&
+        v.timestamp_value.is_none() &&
+        v.string_value.is_none() &&
+        v.bytes_value.is_none() &&
+        v.reference_value.is_none() &&
+        v.geo_point_value.is_none() &&
+        v.array_value.is_none() &&
+        v.map_value.is_none()
+    )
+}
+
+impl<'de> IntoDeserializer<'de, SerdeError> for FieldValue {
+    type Deserializer = FieldValue;
+    fn into_deserializer(self) -> FieldValue {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for FieldValue {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        let v = self.0;
+        if let Some(boolean) = v.boolean_value {
+            visitor.visit_bool(boolean)
+        } else if let Some(integer) = v.integer_value {
+            visitor.visit_i64(integer.parse::<i64>()
+                                     .map_err(|e| <SerdeError as de::Error>::custom(e))?)
+        } else if let Some(double) = v.double_value {
+            visitor.visit_f64(double)
+        } else if let Some(timestamp) = v.timestamp_value {
+            visitor.visit_string(timestamp)
+        } else if let Some(string) = v.string_value {
+            visitor.visit_string(string)
+        } else if let Some(bytes) = v.bytes_value {
+            visitor.visit_string(bytes)
+        } else if let Some(reference) = v.reference_value {
+            visitor.visit_string(reference)
+        } else if let Some(geo_point) = v.geo_point_value {
+            let mut map = MapDeserializer::<_, SerdeError>::new(vec![
+                ("latitude".to_string(), FieldValue::from(geo_point.latitude.unwrap_or(0.0))),
+                ("longitude".to_string(), FieldValue::from(geo_point.longitude.unwrap_or(0.0))),
+            ].into_iter());
+            let value = visitor.visit_map(&mut map)?;
+            map.end()?;
+            Ok(value)
+        } else if let Some(array) = v.array_value {
+            let mut seq = SeqDeserializer::<_, SerdeError>::new(array.values
+                                                    .unwrap_or_default()
+                                                    .into_iter()
+                                                    .map(FieldValue));
+            let value = visitor.visit_seq(&mut seq)?;
+            seq.end()?;
+            Ok(value)
+        } else if let Some(map) = v.map_value {
+            let mut map = MapDeserializer::<_, SerdeError>::new(map.fields
+                                                  .unwrap_or_default()
+                                                  .into_iter()
+                                                  .map(|(k, v)| (MapKey(k), FieldValue(v))));
+            let value = visitor.visit_map(&mut map)?;
+            map.end()?;
+            Ok(value)
+        } else {
+            visitor.visit_unit()
+        }
+    }
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        if is_null(&self.0) {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+        ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+        ) -> Result<V::Value, SerdeError> {
+        let v = self.0;
+        if let Some(variant) = v.string_value {
+            // unit variants are stored by their name
+            visitor.visit_enum(<String as IntoDeserializer<'de, SerdeError>>::into_deserializer(variant))
+        } else {
+            // other variants as a map from their name to their data
+            let mut fields = v.map_value
+                              .and_then(|m| m.fields)
+                              .unwrap_or_default()
+                              .into_iter();
+            match (fields.next(), fields.next()) {
+                (Some((variant, value)), None) =>
+                    visitor.visit_enum(EnumDeserializer(variant, FieldValue(value))),
+                _ => Err(de::Error::custom("expected a string or a map with a single key for an enum")),
+            }
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map
+        struct identifier ignored_any
+    }
+}
+
+// map keys are stored as strings, integer keys
+// are parsed back like serde_json does
+struct MapKey(String);
+
+impl<'de> IntoDeserializer<'de, SerdeError> for MapKey {
+    type Deserializer = MapKey;
+    fn into_deserializer(self) -> MapKey {
+        self
+    }
+}
+
+macro_rules! deserialize_integer_key {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+                match self.0.parse() {
+                    Ok(integer) => visitor.$visit(integer),
+                    Err(_) => visitor.visit_string(self.0),
+                }
+            }
+        )*
+    }
+}
+
+impl<'de> de::Deserializer<'de> for MapKey {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_string(self.0)
+    }
+    deserialize_integer_key! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+    }
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_some(self)
+    }
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+        ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+        ) -> Result<V::Value, SerdeError> {
+        visitor.visit_enum(<String as IntoDeserializer<'de, SerdeError>>::into_deserializer(self.0))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool f32 f64 char str string bytes byte_buf unit unit_struct
+        seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+// an enum variant with its data
+struct EnumDeserializer(String, FieldValue);
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+    type Error = SerdeError;
+    type Variant = FieldValue;
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+        ) -> Result<(V::Value, FieldValue), SerdeError> {
+        let variant = seed.deserialize(
+            <String as IntoDeserializer<'de, SerdeError>>::into_deserializer(self.0))?;
+        Ok((variant, self.1))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for FieldValue {
+    type Error = SerdeError;
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        Ok(())
+    }
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(
+        self,
+        seed: T,
+        ) -> Result<T::Value, SerdeError> {
+        seed.deserialize(self)
+    }
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+        ) -> Result<V::Value, SerdeError> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+        ) -> Result<V::Value, SerdeError> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::{
+        Document,
+        FieldValue,
+    };
+    use serde::{
+        Serialize,
+        Deserialize,
+    };
+    use std::collections::{
+        HashMap,
+        BTreeMap,
+    };
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Kind {
+        Unit,
+        Newtype(i32),
+        Struct {
+            flag: bool,
+        },
+    }
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Inner {
+        ratio: f64,
+        tags: Vec<String>,
+    }
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Test {
+        name: String,
+        count: u32,
+        missing: Option<i64>,
+        present: Option<i64>,
+        inner: Inner,
+        kinds: Vec<Kind>,
+        scores: HashMap<String, i64>,
+    }
+
+    fn test_value() -> Test {
+        let mut scores = HashMap::new();
+        scores.insert("a".to_string(), 1);
+        Test {
+            name: "TestString".to_string(),
+            count: 42,
+            missing: None,
+            present: Some(-3),
+            inner: Inner {
+                ratio: 0.5,
+                tags: vec!["x".to_string(), "y".to_string()],
+            },
+            kinds: vec![
+                Kind::Unit,
+                Kind::Newtype(7),
+                Kind::Struct { flag: true },
+            ],
+            scores,
+        }
+    }
+    #[test]
+    fn document_fields() {
+        let document = Document::from_serialize("Test", &test_value()).unwrap();
+        assert_eq!(document.name(), "Test");
+        assert_eq!(document.get("name"), Ok(&FieldValue::from("TestString")));
+        assert_eq!(document.get("count"), Ok(&FieldValue::from(42)));
+        assert_eq!(document.get("missing"), Ok(&FieldValue::from(())));
+    }
+    #[test]
+    fn round_trip() {
+        let document = Document::from_serialize("Test", &test_value()).unwrap();
+        assert_eq!(document.deserialize::<Test>().unwrap(), test_value());
+    }
+    #[test]
+    fn integer_keys() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Keys {
+            unsigned: HashMap<u32, String>,
+            signed: BTreeMap<i64, bool>,
+        }
+        let mut keys = Keys {
+            unsigned: HashMap::new(),
+            signed: BTreeMap::new(),
+        };
+        keys.unsigned.insert(7, "seven".to_string());
+        keys.signed.insert(-1, true);
+        keys.signed.insert(20, false);
+        let document = Document::from_serialize("Keys", &keys).unwrap();
+        assert_eq!(document.deserialize::<Keys>().unwrap(), keys);
+    }
+    #[test]
+    fn read_null_from_firestore() {
+        let document = Document::builder()
+            .field("value", FieldValue::null_value())
+            .build();
+        #[derive(Deserialize)]
+        struct Nullable {
+            value: Option<String>,
+        }
+        assert_eq!(document.deserialize::<Nullable>().unwrap().value, None);
+    }
+    #[test]
+    fn not_a_map() {
+        Document::from_serialize("Test", &5).expect_err("Serialized an integer as a document!");
+    }
+}
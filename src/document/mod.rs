@@ -1,7 +1,22 @@
 pub mod fields;
+pub mod ser;
+pub mod de;
+pub mod path;
 
 use crate::logger::indent_lines;
 pub use fields::*;
+pub use ser::{
+    to_value,
+    SerdeError,
+};
+pub use de::{
+    from_value,
+};
+pub use path::FieldPath;
+use serde::{
+    Serialize,
+    de::DeserializeOwned,
+};
 use google_firestore;
 use std::collections::HashMap;
 
@@ -11,6 +26,9 @@ pub struct Document {
     fields: HashMap<String, FieldValue>,
     create_time: Option<String>, // should be special types,
     update_time: Option<String>, // convertable to string
+    // the fields which were read when the document was read
+    // with a projection, None if all fields were read
+    projection: Option<Vec<FieldPath>>,
 }
 unsafe impl Send for Document {}
 
@@ -40,10 +58,47 @@ impl Document {
     pub fn get(&self, key: &str) -> Result<&FieldValue, String> {
         self.fields
             .get(key)
-            .ok_or(format!(
-                    "Document {} does not have field \"{}\"",
-                    self.id,
-                    key))
+            .ok_or_else(|| if self.is_projected(key) {
+                format!("Document {} does not have field \"{}\"",
+                        self.id,
+                        key)
+            } else {
+                format!("Field \"{}\" of document {} was not read",
+                        key,
+                        self.id)
+            })
+    }
+    // the fields which were read, None if the document was read
+    // completely. Fields outside of the projection may exist
+    pub fn projection(&self) -> Option<&[FieldPath]> {
+        self.projection.as_ref().map(|p| p.as_slice())
+    }
+    // true if the field was read, so a missing
+    // value means the field does not exist
+    pub fn is_projected(&self, path: &str) -> bool {
+        let segments = FieldPath::from(path).segments();
+        match &self.projection {
+            Some(projection) => projection.iter().any(|p| {
+                let projected = p.segments();
+                projected.len() <= segments.len() &&
+                    projected[..] == segments[..projected.len()]
+            }),
+            None => true,
+        }
+    }
+    // the document with another name
+    pub(crate) fn named(self, name: &str) -> Document {
+        Document {
+            id: name.to_string(),
+            ..self
+        }
+    }
+    // marks the document as read with a projection
+    pub(crate) fn projected(self, mask: Vec<FieldPath>) -> Document {
+        Document {
+            projection: Some(mask),
+            ..self
+        }
     }
     pub fn create_time(&self) -> Option<String> {
         self.create_time.clone()
@@ -51,6 +106,123 @@ impl Document {
     pub fn update_time(&self) -> Option<String> {
         self.update_time.clone()
     }
+    // sets the value at a field path, creating
+    // intermediate maps if they do not exist
+    pub(crate) fn set_field(&mut self, path: &FieldPath, value: FieldValue) {
+        let segments = path.segments();
+        let (first, rest) = segments.split_first().unwrap();
+        if rest.is_empty() {
+            self.fields.insert(first.clone(), value);
+            return;
+        }
+        let (last, parents) = rest.split_last().unwrap();
+        let mut current = &mut self.fields
+            .entry(first.clone())
+            .or_insert_with(FieldValue::default)
+            .0;
+        for segment in parents {
+            current = map_fields(current)
+                .entry(segment.clone())
+                .or_insert_with(google_firestore::Value::default);
+        }
+        map_fields(current).insert(last.clone(), value.0);
+    }
+    // removes the value at a field path, if it exists
+    pub(crate) fn remove_field(&mut self, path: &FieldPath) {
+        let segments = path.segments();
+        let (first, rest) = segments.split_first().unwrap();
+        if rest.is_empty() {
+            self.fields.remove(first);
+            return;
+        }
+        let (last, parents) = rest.split_last().unwrap();
+        let mut current = match self.fields.get_mut(first) {
+            Some(value) => &mut value.0,
+            None => return,
+        };
+        for segment in parents {
+            current = match current.map_value
+                                   .as_mut()
+                                   .and_then(|m| m.fields.as_mut())
+                                   .and_then(|f| f.get_mut(segment)) {
+                Some(value) => value,
+                None => return,
+            };
+        }
+        if let Some(fields) = current.map_value
+                                     .as_mut()
+                                     .and_then(|m| m.fields.as_mut()) {
+            fields.remove(last);
+        }
+    }
+    // the paths of all fields which are not maps with fields,
+    // as they are changed when merging the document into another
+    pub(crate) fn field_paths(&self) -> Vec<FieldPath> {
+        fn leaves(prefix: &mut Vec<String>, value: &google_firestore::Value, paths: &mut Vec<FieldPath>) {
+            match value.map_value.as_ref().and_then(|m| m.fields.as_ref()) {
+                Some(fields) if !fields.is_empty() => for (name, value) in fields {
+                    prefix.push(name.clone());
+                    leaves(prefix, value, paths);
+                    prefix.pop();
+                },
+                _ => paths.push(FieldPath::from_segments(prefix)),
+            }
+        }
+        let mut paths = Vec::new();
+        for (name, value) in &self.fields {
+            leaves(&mut vec![name.clone()], &value.0, &mut paths);
+        }
+        paths
+    }
+    // a copy of the document with only the given fields,
+    // marked as read with the mask as projection
+    pub(crate) fn masked(&self, mask: &[FieldPath]) -> Document {
+        let mut document = Document {
+            fields: HashMap::new(),
+            projection: Some(mask.to_vec()),
+            ..self.clone()
+        };
+        for path in mask {
+            if let Some(value) = crate::query::eval::field_value(self, path.as_str()) {
+                document.set_field(path, value);
+            }
+        }
+        document
+    }
+    // creates a document with the given name from a
+    // serializable struct or map, one field per member
+    pub fn from_serialize<T: Serialize>(name: &str, value: &T) -> Result<Document, SerdeError> {
+        match to_value(value)?.0.map_value {
+            Some(map) => Ok(Document {
+                id: name.to_string(),
+                fields: map.fields
+                    .unwrap_or(HashMap::new())
+                    .into_iter()
+                    .map(|(k, v)| (k, FieldValue::from(v)))
+                    .collect(),
+                ..Document::default()
+            }),
+            None => Err(<SerdeError as serde::ser::Error>::custom(
+                    "Only structs and maps can be serialized as a document")),
+        }
+    }
+    // reads the fields of the document into a deserializable type
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, SerdeError> {
+        from_value(FieldValue::from(self.fields.clone()))
+    }
+}
+
+// the fields of a map value, turning
+// the value into an empty map if it is not one
+fn map_fields(value: &mut google_firestore::Value) -> &mut HashMap<String, google_firestore::Value> {
+    if value.map_value.is_none() {
+        *value = FieldValue::from(HashMap::<String, FieldValue>::new()).0;
+    }
+    value.map_value
+         .as_mut()
+         .unwrap()
+         .fields
+         .get_or_insert_with(HashMap::new)
 }
 
 impl std::convert::From<google_firestore::Document> for Document {
@@ -64,6 +236,7 @@ impl std::convert::From<google_firestore::Document> for Document {
                 .collect(),
             create_time: document.create_time,
             update_time: document.update_time,
+            projection: None,
         }
     }
 }
@@ -88,6 +261,7 @@ impl std::default::Default for Document {
             fields: HashMap::new(),
             create_time: None,
             update_time: None,
+            projection: None,
         }
     }
 }
@@ -0,0 +1,77 @@
+// A FieldPath names a (possibly nested) field of a document,
+// segments are separated by dots and may be quoted with backticks,
+// e.g. "address.city" or "prices.`1.5`"
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FieldPath(String);
+
+impl FieldPath {
+    pub fn new<T: ToString>(path: T) -> Self {
+        FieldPath(path.to_string())
+    }
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+    // the path of nested fields, quoting names
+    // which are not simple identifiers
+    pub fn from_segments<T: AsRef<str>>(segments: &[T]) -> Self {
+        FieldPath(segments.iter()
+                          .map(|s| quote(s.as_ref()))
+                          .collect::<Vec<_>>()
+                          .join("."))
+    }
+    // the unquoted names of the nested fields
+    pub fn segments(&self) -> Vec<String> {
+        let mut segments = Vec::new();
+        let mut segment = String::new();
+        let mut quoted = false;
+        let mut chars = self.0.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '`' => quoted = !quoted,
+                '\\' if quoted => segment.extend(chars.next()),
+                '.' if !quoted => segments.push(std::mem::replace(&mut segment, String::new())),
+                c => segment.push(c),
+            }
+        }
+        segments.push(segment);
+        segments
+    }
+}
+
+fn quote(segment: &str) -> String {
+    let simple = segment.chars().next().map(|c| !c.is_ascii_digit()).unwrap_or(false) &&
+        segment.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if simple {
+        segment.to_string()
+    } else {
+        format!("`{}`", segment.replace("\\", "\\\\").replace("`", "\\`"))
+    }
+}
+
+impl From<&str> for FieldPath {
+    fn from(path: &str) -> Self {
+        FieldPath::new(path)
+    }
+}
+impl From<String> for FieldPath {
+    fn from(path: String) -> Self {
+        FieldPath(path)
+    }
+}
+impl std::fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn segments() {
+        let segments = vec!["address", "1.5", "a`b", "_id"];
+        let path = FieldPath::from_segments(&segments);
+        assert_eq!(path.as_str(), "address.`1.5`.`a\\`b`._id");
+        assert_eq!(path.segments(), segments);
+    }
+}
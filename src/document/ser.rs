@@ -0,0 +1,302 @@
+use super::FieldValue;
+use serde::ser::{
+    self,
+    Serialize,
+};
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+// error when mapping between Rust values and FieldValues
+#[derive(Debug, Clone, PartialEq)]
+pub struct SerdeError(String);
+
+impl Display for SerdeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+impl std::error::Error for SerdeError {}
+impl ser::Error for SerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        SerdeError(msg.to_string())
+    }
+}
+impl serde::de::Error for SerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        SerdeError(msg.to_string())
+    }
+}
+
+// converts any serializable value to a FieldValue.
+// structs and maps become map values, sequences and tuples
+// become array values and enum variants with data become
+// maps with the variant name as their only key
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<FieldValue, SerdeError> {
+    value.serialize(ValueSerializer)
+}
+
+fn variant_value(variant: &str, value: FieldValue) -> FieldValue {
+    let mut map = HashMap::new();
+    map.insert(variant.to_string(), value);
+    FieldValue::from(map)
+}
+
+// map keys have to be strings in Firestore,
+// integer keys are converted to strings
+fn key_string<T: Serialize + ?Sized>(key: &T) -> Result<String, SerdeError> {
+    match to_value(key)?.0 {
+        google_firestore::Value {
+            string_value: Some(key),
+            ..
+        } => Ok(key),
+        google_firestore::Value {
+            integer_value: Some(key),
+            ..
+        } => Ok(key),
+        _ => Err(ser::Error::custom("map keys must be strings or integers")),
+    }
+}
+
+pub struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = FieldValue;
+    type Error = SerdeError;
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeTupleVariant;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeStructVariant;
+
+    fn serialize_bool(self, v: bool) -> Result<FieldValue, SerdeError> {
+        Ok(FieldValue::from(v))
+    }
+    fn serialize_i8(self, v: i8) -> Result<FieldValue, SerdeError> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_i16(self, v: i16) -> Result<FieldValue, SerdeError> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_i32(self, v: i32) -> Result<FieldValue, SerdeError> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_i64(self, v: i64) -> Result<FieldValue, SerdeError> {
+        Ok(FieldValue::from(v))
+    }
+    fn serialize_u8(self, v: u8) -> Result<FieldValue, SerdeError> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_u16(self, v: u16) -> Result<FieldValue, SerdeError> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_u32(self, v: u32) -> Result<FieldValue, SerdeError> {
+        self.serialize_i64(v as i64)
+    }
+    fn serialize_u64(self, v: u64) -> Result<FieldValue, SerdeError> {
+        if v > std::i64::MAX as u64 {
+            Err(ser::Error::custom(format!("{} does not fit into a 64 bit integer value", v)))
+        } else {
+            self.serialize_i64(v as i64)
+        }
+    }
+    fn serialize_f32(self, v: f32) -> Result<FieldValue, SerdeError> {
+        self.serialize_f64(v as f64)
+    }
+    fn serialize_f64(self, v: f64) -> Result<FieldValue, SerdeError> {
+        Ok(FieldValue::from(v))
+    }
+    fn serialize_char(self, v: char) -> Result<FieldValue, SerdeError> {
+        Ok(FieldValue::from(v.to_string()))
+    }
+    fn serialize_str(self, v: &str) -> Result<FieldValue, SerdeError> {
+        Ok(FieldValue::from(v))
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<FieldValue, SerdeError> {
+        // stored as an array of integers, like serde_json does
+        Ok(FieldValue::from(v.iter()
+                             .map(|b| FieldValue::from(*b as i64))
+                             .collect::<Vec<FieldValue>>()))
+    }
+    fn serialize_none(self) -> Result<FieldValue, SerdeError> {
+        self.serialize_unit()
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<FieldValue, SerdeError> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<FieldValue, SerdeError> {
+        Ok(FieldValue::from(()))
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<FieldValue, SerdeError> {
+        self.serialize_unit()
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        ) -> Result<FieldValue, SerdeError> {
+        self.serialize_str(variant)
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+        ) -> Result<FieldValue, SerdeError> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+        ) -> Result<FieldValue, SerdeError> {
+        Ok(variant_value(variant, to_value(value)?))
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray, SerdeError> {
+        Ok(SerializeArray(Vec::with_capacity(len.unwrap_or(0))))
+    }
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+        ) -> Result<SerializeArray, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+        ) -> Result<SerializeTupleVariant, SerdeError> {
+        Ok(SerializeTupleVariant(variant, Vec::with_capacity(len)))
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeMap, SerdeError> {
+        Ok(SerializeMap(HashMap::new(), None))
+    }
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+        ) -> Result<SerializeMap, SerdeError> {
+        self.serialize_map(Some(len))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+        ) -> Result<SerializeStructVariant, SerdeError> {
+        Ok(SerializeStructVariant(variant, HashMap::new()))
+    }
+}
+
+pub struct SerializeArray(Vec<FieldValue>);
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = FieldValue;
+    type Error = SerdeError;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.0.push(to_value(value)?);
+        Ok(())
+    }
+    fn end(self) -> Result<FieldValue, SerdeError> {
+        Ok(FieldValue::from(self.0))
+    }
+}
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = FieldValue;
+    type Error = SerdeError;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<FieldValue, SerdeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = FieldValue;
+    type Error = SerdeError;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<FieldValue, SerdeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+pub struct SerializeTupleVariant(&'static str, Vec<FieldValue>);
+
+impl ser::SerializeTupleVariant for SerializeTupleVariant {
+    type Ok = FieldValue;
+    type Error = SerdeError;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.1.push(to_value(value)?);
+        Ok(())
+    }
+    fn end(self) -> Result<FieldValue, SerdeError> {
+        Ok(variant_value(self.0, FieldValue::from(self.1)))
+    }
+}
+
+// the fields serialized so far and the key
+// waiting for its value
+pub struct SerializeMap(HashMap<String, FieldValue>, Option<String>);
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = FieldValue;
+    type Error = SerdeError;
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        self.1 = Some(key_string(key)?);
+        Ok(())
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let key = self.1.take()
+            .ok_or_else(|| SerdeError("serialize_value called before serialize_key".to_string()))?;
+        self.0.insert(key, to_value(value)?);
+        Ok(())
+    }
+    fn end(self) -> Result<FieldValue, SerdeError> {
+        Ok(FieldValue::from(self.0))
+    }
+}
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = FieldValue;
+    type Error = SerdeError;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+        ) -> Result<(), SerdeError> {
+        self.0.insert(key.to_string(), to_value(value)?);
+        Ok(())
+    }
+    fn end(self) -> Result<FieldValue, SerdeError> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+pub struct SerializeStructVariant(&'static str, HashMap<String, FieldValue>);
+
+impl ser::SerializeStructVariant for SerializeStructVariant {
+    type Ok = FieldValue;
+    type Error = SerdeError;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+        ) -> Result<(), SerdeError> {
+        self.1.insert(key.to_string(), to_value(value)?);
+        Ok(())
+    }
+    fn end(self) -> Result<FieldValue, SerdeError> {
+        Ok(variant_value(self.0, FieldValue::from(self.1)))
+    }
+}
@@ -1,30 +1,152 @@
-pub struct DatabaseError(google_firestore::Error);
+use std::fmt::{Debug, Display, Formatter, self};
 
-unsafe impl Send for DatabaseError {}
-unsafe impl Sync for DatabaseError {}
+// the kind of failure, independent of the backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatabaseErrorKind {
+    NotFound,
+    AlreadyExists,
+    PermissionDenied,
+    // the system is not in the state the operation requires
+    FailedPrecondition,
+    // the update time precondition of a write was not met,
+    // the document was changed since it was read
+    PreconditionFailed,
+    // aborted because of contention, may be retried
+    Aborted,
+    Unavailable,
+    InvalidArgument,
+    Cancelled,
+    // the request could not be sent or the response not received
+    Transport,
+    // no valid credentials
+    Auth,
+    // the response could not be decoded
+    Decode,
+    Other,
+}
 
-impl From<google_firestore::Error> for DatabaseError {
-    fn from(err: google_firestore::Error) -> Self {
-        DatabaseError(err)
+impl DatabaseErrorKind {
+    // the kind of a gRPC status name, as returned
+    // in the "status" field of Firestore errors
+    pub fn from_status(status: &str) -> Self {
+        match status {
+            "NOT_FOUND" => DatabaseErrorKind::NotFound,
+            "ALREADY_EXISTS" => DatabaseErrorKind::AlreadyExists,
+            "PERMISSION_DENIED" => DatabaseErrorKind::PermissionDenied,
+            "UNAUTHENTICATED" => DatabaseErrorKind::Auth,
+            "FAILED_PRECONDITION" => DatabaseErrorKind::FailedPrecondition,
+            "ABORTED" => DatabaseErrorKind::Aborted,
+            "UNAVAILABLE" |
+            "DEADLINE_EXCEEDED" => DatabaseErrorKind::Unavailable,
+            "INVALID_ARGUMENT" |
+            "OUT_OF_RANGE" => DatabaseErrorKind::InvalidArgument,
+            "CANCELLED" => DatabaseErrorKind::Cancelled,
+            _ => DatabaseErrorKind::Other,
+        }
+    }
+    // the kind of a Firestore error response
+    // like {"error": {"code", "message", "status"}}
+    pub(crate) fn from_response(response: &json::Value) -> Self {
+        let error = &response["error"];
+        match error["status"].as_str() {
+            Some(status) => DatabaseErrorKind::from_status(status),
+            None => DatabaseErrorKind::from_http_status(
+                error["code"].as_u64().unwrap_or(0) as u16,
+                error["message"].as_str().unwrap_or("")),
+        }
+    }
+    // the kind of an HTTP status code, for errors without a status name.
+    // 409 is used for both ALREADY_EXISTS and ABORTED
+    pub fn from_http_status(code: u16, message: &str) -> Self {
+        match code {
+            400 => DatabaseErrorKind::InvalidArgument,
+            401 => DatabaseErrorKind::Auth,
+            403 => DatabaseErrorKind::PermissionDenied,
+            404 => DatabaseErrorKind::NotFound,
+            409 => if message.to_lowercase().contains("already exists") {
+                       DatabaseErrorKind::AlreadyExists
+                   } else {
+                       DatabaseErrorKind::Aborted
+                   },
+            412 => DatabaseErrorKind::FailedPrecondition,
+            499 => DatabaseErrorKind::Cancelled,
+            503 | 504 => DatabaseErrorKind::Unavailable,
+            _ => DatabaseErrorKind::Other,
+        }
     }
 }
-use actix_web::error::BlockingError;
-impl<E: Into<DatabaseError> + Debug> From<BlockingError<E>> for DatabaseError {
-    fn from(err: BlockingError<E>) -> Self {
-        match err {
-            BlockingError::Error(e) => e.into(),
-            BlockingError::Canceled => google_firestore::Error::Cancelled.into(),
+
+#[derive(Clone)]
+pub struct DatabaseError {
+    kind: DatabaseErrorKind,
+    message: String,
+}
+
+impl DatabaseError {
+    pub fn new<T: ToString>(kind: DatabaseErrorKind, message: T) -> Self {
+        DatabaseError {
+            kind,
+            message: message.to_string(),
         }
     }
+    pub fn kind(&self) -> DatabaseErrorKind {
+        self.kind
+    }
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+    // true if the operation was aborted because of
+    // contention with other transactions and may be retried
+    pub fn is_aborted(&self) -> bool {
+        self.kind == DatabaseErrorKind::Aborted
+    }
+}
+
+impl From<google_firestore::Error> for DatabaseError {
+    fn from(err: google_firestore::Error) -> Self {
+        use google_firestore::Error;
+        let kind = match &err {
+            Error::HttpError(_) => DatabaseErrorKind::Transport,
+            Error::UploadSizeLimitExceeded(..) |
+            Error::FieldClash(_) => DatabaseErrorKind::InvalidArgument,
+            Error::MissingAPIKey |
+            Error::MissingToken(_) => DatabaseErrorKind::Auth,
+            Error::Cancelled => DatabaseErrorKind::Cancelled,
+            Error::JsonDecodeError(..) => DatabaseErrorKind::Decode,
+            Error::BadRequest(response) => json::to_value(response)
+                .map(|response| DatabaseErrorKind::from_response(&response))
+                .unwrap_or(DatabaseErrorKind::Other),
+            Error::Failure(response) =>
+                DatabaseErrorKind::from_http_status(response.status.to_u16(), ""),
+        };
+        DatabaseError::new(kind, err)
+    }
 }
-use std::fmt::{Debug, Display, Formatter, self};
 impl Debug for DatabaseError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "DatabaseError({})", self.0)
+        write!(f, "DatabaseError({:?}: {})", self.kind, self.message)
     }
 }
 impl Display for DatabaseError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "DatabaseError: {}", self.0)
+        write!(f, "DatabaseError: {}", self.message)
+    }
+}
+impl std::error::Error for DatabaseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn kinds() {
+        assert_eq!(DatabaseErrorKind::from_status("ABORTED"), DatabaseErrorKind::Aborted);
+        assert_eq!(DatabaseErrorKind::from_status("UNAUTHENTICATED"), DatabaseErrorKind::Auth);
+        assert_eq!(DatabaseErrorKind::from_http_status(404, ""), DatabaseErrorKind::NotFound);
+        assert_eq!(DatabaseErrorKind::from_http_status(409, "Document already exists: a/b"),
+                   DatabaseErrorKind::AlreadyExists);
+        assert_eq!(DatabaseErrorKind::from_http_status(409, "Too much contention"),
+                   DatabaseErrorKind::Aborted);
+        let error = DatabaseError::from(google_firestore::Error::Cancelled);
+        assert_eq!(error.kind(), DatabaseErrorKind::Cancelled);
     }
 }
@@ -1,27 +1,125 @@
 use crate::access::{Access};
 use crate::client::{Client};
+use crate::error::{DatabaseError, DatabaseErrorKind};
+use crate::firestore::credentials::{AuthorizedUserAccess};
 use oauth2::{Token, GetToken};
+use std::convert::TryFrom;
 use std::env;
 use std::fs;
 use std::io::Read;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 pub use oauth2::{ServiceAccountAccess, ServiceAccountKey};
 use std::error::Error;
 
-pub type FirestoreAccess = Access<ServiceAccountAccess<Client>>;
+// the credentials used to authenticate requests
+pub enum Credentials {
+    ServiceAccount(ServiceAccountAccess<Client>),
+    // a user's credentials, as created by `gcloud auth application-default login`
+    AuthorizedUser(AuthorizedUserAccess),
+    // an access token obtained elsewhere, used as is
+    AccessToken(String),
+    // the Firestore emulator accepts any requests,
+    // so no OAuth tokens need to be requested
+    Emulator,
+}
 
-impl From<ServiceAccountKey> for FirestoreAccess {
-    fn from(key: ServiceAccountKey) -> Self {
-        let access = ServiceAccountAccess::new(key.clone(), Client::default());
-        let project_id = key.project_id.unwrap_or_else(|| {
-            panic!("No project_id in service account key!");
-        });
-        Self {
+pub type FirestoreAccess = Access<Credentials>;
+
+impl TryFrom<ServiceAccountKey> for FirestoreAccess {
+    type Error = DatabaseError;
+    fn try_from(key: ServiceAccountKey) -> Result<Self, Self::Error> {
+        let project_id = key.project_id.clone().ok_or_else(|| DatabaseError::new(
+                DatabaseErrorKind::Auth,
+                "No project_id in service account key"))?;
+        let access = ServiceAccountAccess::new(key, Client::default());
+        Ok(Self {
             project_id,
-            access
+            access: Credentials::ServiceAccount(access),
+        })
+    }
+}
+
+impl FirestoreAccess {
+    // access to the emulator at FIRESTORE_EMULATOR_HOST,
+    // for the project in GCLOUD_PROJECT (if set)
+    pub fn emulator() -> Self {
+        Self {
+            project_id: env::var("GCLOUD_PROJECT")
+                .unwrap_or("emulator".to_string()),
+            access: Credentials::Emulator,
         }
     }
 }
 
+// the host (e.g. "localhost:8080") of the Firestore emulator,
+// if the FIRESTORE_EMULATOR_HOST environment variable is set
+pub fn emulator_host() -> Option<String> {
+    env::var("FIRESTORE_EMULATOR_HOST")
+        .ok()
+        .filter(|host| !host.is_empty())
+}
+
+impl GetToken for Credentials {
+    fn token<'b, I, T>(&mut self, scopes: I) -> Result<Token, Box<dyn Error>>
+    where
+        T: AsRef<str> + Ord + 'b,
+        I: IntoIterator<Item = &'b T>,
+    {
+        match self {
+            Credentials::ServiceAccount(access) => access.token(scopes),
+            Credentials::AuthorizedUser(access) => access.token(scopes),
+            Credentials::AccessToken(token) => Ok(Token {
+                access_token: token.clone(),
+                refresh_token: String::new(),
+                token_type: "Bearer".to_string(),
+                expires_in: None,
+                expires_in_timestamp: None,
+            }),
+            // the emulator treats the "owner" token as an admin
+            Credentials::Emulator => Ok(Token {
+                access_token: "owner".to_string(),
+                refresh_token: String::new(),
+                token_type: "Bearer".to_string(),
+                expires_in: None,
+                expires_in_timestamp: None,
+            }),
+        }
+    }
+
+    fn api_key(&mut self) -> Option<String> {
+        match self {
+            Credentials::ServiceAccount(access) => access.api_key(),
+            _ => None,
+        }
+    }
+}
+
+// credentials shared by multiple hubs, so
+// tokens are requested once and cached for all
+#[derive(Clone)]
+pub struct SharedAccess(Arc<Mutex<FirestoreAccess>>);
+
+impl SharedAccess {
+    pub fn new(access: FirestoreAccess) -> Self {
+        SharedAccess(Arc::new(Mutex::new(access)))
+    }
+}
+
+impl GetToken for SharedAccess {
+    fn token<'b, I, T>(&mut self, scopes: I) -> Result<Token, Box<dyn Error>>
+    where
+        T: AsRef<str> + Ord + 'b,
+        I: IntoIterator<Item = &'b T>,
+    {
+        self.0.lock().unwrap().token(scopes)
+    }
+
+    fn api_key(&mut self) -> Option<String> {
+        self.0.lock().unwrap().api_key()
+    }
+}
+
 impl<A: GetToken> GetToken for Access<A> {
     fn token<'b, I, T>(&mut self, scopes: I) -> Result<Token, Box<dyn Error>>
     where
@@ -36,29 +134,35 @@ impl<A: GetToken> GetToken for Access<A> {
     }
 }
 
-fn read_service_account_key(file: std::fs::File) -> Result<ServiceAccountKey, json::Error> {
-    let mut file = file;
+// reads a credentials file into a string
+pub(crate) fn read_credentials_file<P: AsRef<Path>>(path: P) -> Result<String, DatabaseError> {
+    let path = path.as_ref();
+    info!("Reading credentials from '{}'", path.display());
     let mut content = String::new();
-    file.read_to_string(&mut content).unwrap_or_else(|e| {
-        panic!("{}", e);
-    });
+    fs::File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut content))
+        .map_err(|e| DatabaseError::new(
+                DatabaseErrorKind::Auth,
+                format!("Can't read credentials file '{}': {}", path.display(), e)))?;
+    Ok(content)
+}
+
+pub fn read_service_account_key<P: AsRef<Path>>(path: P) -> Result<ServiceAccountKey, DatabaseError> {
+    let content = read_credentials_file(&path)?;
     json::from_str::<ServiceAccountKey>(&content)
+        .map_err(|e| DatabaseError::new(
+                DatabaseErrorKind::Auth,
+                format!("Invalid service account key '{}': {}", path.as_ref().display(), e)))
 }
 
-pub fn get_service_account_key() -> ServiceAccountKey {
-    let path = env::var("GOOGLE_APPLICATION_CREDENTIALS").unwrap_or_else(|e| {
-        panic!(
-            "Can't read credential json file: ({})\n\
-             Please set GOOGLE_APPLICATION_CREDENTIALS \
-             environment variable!",
-            e
-        );
-    });
-    info!("Reading Service Account Key from '{}'", path);
-    let file = fs::File::open(path).unwrap_or_else(|e| {
-        panic!("{}", e);
-    });
-    read_service_account_key(file).unwrap_or_else(|e| {
-        panic!("{}", e);
-    })
+// reads the service account key at GOOGLE_APPLICATION_CREDENTIALS
+pub fn get_service_account_key() -> Result<ServiceAccountKey, DatabaseError> {
+    let path = env::var("GOOGLE_APPLICATION_CREDENTIALS").map_err(|e|
+        DatabaseError::new(
+            DatabaseErrorKind::Auth,
+            format!("Can't read credential json file: ({})\n\
+                     Please set GOOGLE_APPLICATION_CREDENTIALS \
+                     environment variable!",
+                    e)))?;
+    read_service_account_key(path)
 }
@@ -0,0 +1,160 @@
+use crate::{
+    database::DatabaseFuture,
+    document::FieldValue,
+    error::{
+        DatabaseError,
+        DatabaseErrorKind,
+    },
+    blocking::blocking,
+    firestore::query::FirestoreQuery,
+    query::{
+        eval,
+        aggregation::{
+            Aggregate,
+            Sum,
+        },
+    },
+};
+use std::convert::TryFrom;
+
+// the alias of the single aggregation of a request
+const ALIAS: &str = "result";
+
+impl FirestoreQuery {
+    // the runAggregationQuery request for an aggregation
+    // like {"count": {}} over the results of the query
+    fn aggregation_request(&self, aggregation: json::Value) -> json::Value {
+        let query = FirestoreQuery {
+            select: None,
+            ..self.clone()
+        };
+        let mut aggregation = aggregation;
+        aggregation["alias"] = json::Value::from(ALIAS);
+        json::json!({
+            "structuredAggregationQuery": {
+                "structuredQuery": query.request().structured_query,
+                "aggregations": [aggregation],
+            }
+        })
+    }
+    // runs the aggregation and returns its result value
+    fn aggregate(self, aggregation: json::Value) -> DatabaseFuture<FieldValue> {
+        blocking(move || {
+            let database = self.database.as_ref().ok_or_else(|| DatabaseError::new(
+                    DatabaseErrorKind::InvalidArgument,
+                    "The query has no database to run on, create it with Firestore::query"))?;
+            let response = database.db.post(
+                &format!("{}:runAggregationQuery", self.parent_path(database)),
+                &self.aggregation_request(aggregation))?;
+            aggregation_result(&response)
+        })
+    }
+}
+// the result value of a runAggregationQuery response. The response
+// is a list of partial results, one of which contains the aggregation
+fn aggregation_result(response: &json::Value) -> Result<FieldValue, DatabaseError> {
+    response.as_array()
+        .into_iter()
+        .flatten()
+        .find_map(|partial| partial["result"]["aggregateFields"].get(ALIAS))
+        .map(|value| json::from_value::<google_firestore::Value>(value.clone())
+                         .map(FieldValue::from)
+                         .map_err(|e| DatabaseError::new(DatabaseErrorKind::Decode, e)))
+        .unwrap_or_else(|| Err(DatabaseError::new(
+                    DatabaseErrorKind::Decode,
+                    format!("No aggregation result in response: {}", response))))
+}
+// the average of no numbers is null
+fn average(value: FieldValue) -> Result<Option<f64>, DatabaseError> {
+    if eval::is_null(&value) {
+        return Ok(None);
+    }
+    f64::try_from(value)
+        .map(Some)
+        .map_err(|e| DatabaseError::new(DatabaseErrorKind::Decode, e))
+}
+fn field(field: &str) -> json::Value {
+    json::json!({ "field": { "fieldPath": field } })
+}
+
+impl Aggregate for FirestoreQuery {
+    fn count(self) -> DatabaseFuture<u64> {
+        let result = self.aggregate(json::json!({ "count": {} }));
+        Box::pin(async move {
+            let value = result.await?;
+            i64::try_from(value)
+                .map(|count| count as u64)
+                .map_err(|e| DatabaseError::new(DatabaseErrorKind::Decode, e))
+        })
+    }
+    fn sum(self, field_path: &str) -> DatabaseFuture<Sum> {
+        let result = self.aggregate(json::json!({ "sum": field(field_path) }));
+        Box::pin(async move {
+            Sum::try_from(result.await?)
+        })
+    }
+    fn avg(self, field_path: &str) -> DatabaseFuture<Option<f64>> {
+        let result = self.aggregate(json::json!({ "avg": field(field_path) }));
+        Box::pin(async move {
+            average(result.await?)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::Database,
+        firestore::Firestore,
+        query::Query,
+        query::filter::{Filter, FilterOp},
+        firestore::query::CollectionSelector,
+    };
+    #[test]
+    fn request() {
+        let database = Firestore::with_emulator("project", "localhost:8080");
+        let request = database.query()
+            .collections(vec![CollectionSelector::from("test")])
+            .filter("integer", FilterOp::GREATER_THAN(5))
+            .select(&["integer"])
+            .aggregation_request(json::json!({ "sum": field("integer") }));
+        let aggregation = &request["structuredAggregationQuery"];
+        assert_eq!(aggregation["aggregations"],
+                   json::json!([{ "alias": ALIAS, "sum": { "field": { "fieldPath": "integer" } } }]));
+        assert_eq!(aggregation["structuredQuery"]["from"][0]["collectionId"], "test");
+        assert!(aggregation["structuredQuery"]["where"].is_object());
+        assert!(aggregation["structuredQuery"]["select"].is_null());
+    }
+    // a partial result with a read time only, then the aggregation
+    fn response(value: json::Value) -> json::Value {
+        json::json!([
+            { "readTime": "2024-01-01T00:00:00.000000Z" },
+            {
+                "result": { "aggregateFields": { ALIAS: value } },
+                "readTime": "2024-01-01T00:00:00.000000Z",
+            },
+        ])
+    }
+    #[test]
+    fn decode_count() {
+        let value = aggregation_result(&response(json::json!({ "integerValue": "3" }))).unwrap();
+        assert_eq!(i64::try_from(value).unwrap(), 3);
+    }
+    #[test]
+    fn decode_avg() {
+        let value = aggregation_result(&response(json::json!({ "doubleValue": 2.5 }))).unwrap();
+        assert_eq!(average(value).unwrap(), Some(2.5));
+    }
+    #[test]
+    fn decode_null_avg() {
+        let value = aggregation_result(&response(json::json!({ "nullValue": null }))).unwrap();
+        assert_eq!(average(value).unwrap(), None);
+    }
+    #[test]
+    fn decode_missing_result() {
+        let error = aggregation_result(&json::json!([{ "readTime": "2024-01-01T00:00:00.000000Z" }]))
+            .expect_err("Decoded a response without a result!");
+        assert_eq!(error.kind(), DatabaseErrorKind::Decode);
+    }
+}
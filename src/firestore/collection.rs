@@ -1,23 +1,21 @@
 use crate::{
-    database::Database,
-    error::DatabaseError,
-    document::{Document},
+    database::{Database, DatabaseFuture, DocumentStream, Lookup},
+    list::{ListOptions},
+    document::{Document, FieldPath},
+    write::{FieldTransform, Precondition, SetOptions},
     firestore::{Firestore},
     collection::{Collection},
 };
 use std::vec::Vec;
-use futures::future::{
-    Future,
-};
 
 pub struct FirestoreCollection<'a> {
     firestore: &'a Firestore,
     collection_id: String,
 }
 
-impl Collection<'static> for FirestoreCollection<'static> {
+impl<'a> Collection<'a> for FirestoreCollection<'a> {
     type Database = Firestore;
-    fn new<T: ToString>(database: &'static Firestore, id: T) -> Self {
+    fn new<T: ToString>(database: &'a Firestore, id: T) -> Self {
         Self {
             firestore: database,
             collection_id: id.to_string(),
@@ -31,26 +29,87 @@ impl Collection<'static> for FirestoreCollection<'static> {
     fn get_document<T: ToString>(
         &self,
         document_id: T,
-        ) -> Box<dyn Future<Item=Document, Error=DatabaseError> + Send> {
+        ) -> DatabaseFuture<Document> {
         self.firestore.get_document(self.collection_id.clone(), document_id)
     }
+    fn get_document_masked<T: ToString>(
+        &self,
+        document_id: T,
+        mask: Option<Vec<FieldPath>>,
+        ) -> DatabaseFuture<Document> {
+        self.firestore.get_document_masked(self.collection_id.clone(), document_id, mask)
+    }
+
+    fn get_documents_by_id<T: ToString>(
+        &self,
+        document_ids: &[T],
+        ) -> DatabaseFuture<Vec<Lookup>> {
+        let paths: Vec<String> = document_ids.iter()
+            .map(|id| format!("{}/{}", self.collection_id, id.to_string()))
+            .collect();
+        self.firestore.get_documents_by_id(&paths)
+    }
 
     fn create_document(
         &self,
         document: Document,
-        ) -> Box<dyn Future<Item=String, Error=DatabaseError> + Send> {
+        ) -> DatabaseFuture<String> {
         self.firestore.create_document(self.collection_id.clone(), document)
     }
+    fn set_document(
+        &self,
+        document: Document,
+        options: SetOptions,
+        ) -> DatabaseFuture<()> {
+        self.firestore.set_document(self.collection_id.clone(), document, options)
+    }
+    fn update_document<T: ToString>(
+        &self,
+        document_id: T,
+        document: Document,
+        mask: Option<Vec<FieldPath>>,
+        ) -> DatabaseFuture<Document> {
+        self.firestore.update_document(self.collection_id.clone(), document_id, document, mask)
+    }
+    fn update_document_if<T: ToString>(
+        &self,
+        document_id: T,
+        document: Document,
+        mask: Option<Vec<FieldPath>>,
+        precondition: Precondition,
+        ) -> DatabaseFuture<Document> {
+        self.firestore.update_document_if(self.collection_id.clone(), document_id, document, mask, precondition)
+    }
     fn delete_document<T: ToString>(
         &self,
         document_id: T,
-        ) -> Box<dyn Future<Item=(), Error=DatabaseError> + Send> {
+        ) -> DatabaseFuture<()> {
         self.firestore.delete_document(self.collection_id.clone(), document_id)
     }
+    fn delete_document_if<T: ToString>(
+        &self,
+        document_id: T,
+        precondition: Option<Precondition>,
+        ) -> DatabaseFuture<()> {
+        self.firestore.delete_document_if(self.collection_id.clone(), document_id, precondition)
+    }
+    fn transform_document<T: ToString>(
+        &self,
+        document_id: T,
+        transforms: Vec<FieldTransform>,
+        ) -> DatabaseFuture<()> {
+        self.firestore.transform_document(self.collection_id.clone(), document_id, transforms)
+    }
 
+    fn list_documents(
+        &self,
+        options: ListOptions,
+        ) -> DocumentStream {
+        self.firestore.list_documents(self.collection_id.clone(), options)
+    }
     fn get_documents(
         &self
-        ) -> Box<dyn Future<Item=Vec<Document>, Error=DatabaseError>> {
+        ) -> DatabaseFuture<Vec<Document>> {
         self.firestore.get_documents(self.collection_id.clone())
     }
 }
@@ -59,37 +118,70 @@ impl Collection<'static> for FirestoreCollection<'static> {
 mod tests {
     #![allow(unused)]
     use crate::{
-        collection,
+        try_collection,
         document::{
             Document,
+            FieldPath,
             tests::{
                 test_document,
             },
         },
     };
     use super::*;
+    use futures::executor::block_on;
+    fn collection(id: &str) -> FirestoreCollection<'static> {
+        try_collection(id).unwrap()
+    }
     #[test]
     fn access_collection() {
         let collection = collection("test");
     }
     #[test]
     fn get_document() {
-        let id = format!("{}/{}",
-                         "test",
-                         "ArrayContains");
-        collection("test").get_document(id).wait().unwrap();
+        block_on(async {
+            let id = format!("{}/{}",
+                             "test",
+                             "ArrayContains");
+            collection("test").get_document(id).await.unwrap();
+        })
     }
     #[test]
     fn document_test() {
-        let id =  "TestDocument";
-        let doc = test_document(format!("{}/{}", collection("test").get_path(), id));
-        // delete to avoid possible conflict from previous tests
-        collection("test").delete_document(id.clone()).wait().unwrap();
-        collection("test").create_document(doc.clone()).wait().unwrap();
-        let created = collection("test").get_document(id.clone()).wait().unwrap();
-        assert_eq!(created, doc);
-        collection("test").delete_document(id.clone()).wait().unwrap();
-        collection("test").get_document(id)
-            .wait().expect_err("Got document after calling DELETE!");
+        block_on(async {
+            let id =  "TestDocument";
+            let doc = test_document(format!("{}/{}", collection("test").get_path(), id));
+            // delete to avoid possible conflict from previous tests
+            collection("test").delete_document(id.clone()).await.unwrap();
+            collection("test").create_document(doc.clone()).await.unwrap();
+            let created = collection("test").get_document(id.clone()).await.unwrap();
+            assert_eq!(created, doc);
+            collection("test").delete_document(id.clone()).await.unwrap();
+            collection("test").get_document(id)
+                .await.expect_err("Got document after calling DELETE!");
+        })
+    }
+    #[test]
+    fn update_test() {
+        block_on(async {
+            let id =  "TestUpdateDocument";
+            let doc = test_document(format!("{}/{}", collection("test").get_path(), id));
+            collection("test").delete_document(id.clone()).await.unwrap();
+            collection("test").create_document(doc.clone()).await.unwrap();
+            let update = Document::builder()
+                .field("test_number", 43)
+                .build();
+            collection("test").update_document(id.clone(),
+                                               update,
+                                               Some(vec![FieldPath::from("test_number")]))
+                .await.unwrap();
+            let updated = collection("test").get_document(id.clone()).await.unwrap();
+            let expected = Document::builder()
+                .name(doc.id())
+                .field("test_string", "TestString")
+                .field("test_number", 43)
+                .build();
+            assert_eq!(updated, expected);
+            collection("test").delete_document(id.clone()).await.unwrap();
+        })
     }
 }
@@ -0,0 +1,357 @@
+use crate::client::{Client};
+use crate::error::{DatabaseError, DatabaseErrorKind};
+use super::access::{
+    Credentials,
+    FirestoreAccess,
+    ServiceAccountKey,
+    emulator_host,
+    read_credentials_file,
+};
+use oauth2::{Token, GetToken};
+use serde::{Deserialize};
+use std::convert::TryFrom;
+use std::env;
+use std::error::Error;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+
+// a source of credentials. Returns Ok(None) if the source is not
+// configured, so the next provider of a chain can be tried,
+// and an error if it is configured but the credentials are invalid
+pub trait CredentialProvider {
+    fn credentials(&self) -> Result<Option<FirestoreAccess>, DatabaseError>;
+}
+
+fn auth_error<T: ToString>(message: T) -> DatabaseError {
+    DatabaseError::new(DatabaseErrorKind::Auth, message)
+}
+
+// the project to use with credentials which don't name one
+fn project_from_env() -> Option<String> {
+    env::var("GOOGLE_CLOUD_PROJECT")
+        .or_else(|_| env::var("GCLOUD_PROJECT"))
+        .ok()
+        .filter(|project| !project.is_empty())
+}
+
+// the contents of a credentials file, as written by
+// `gcloud auth application-default login` or for service accounts
+#[derive(Deserialize, Clone, Debug)]
+pub struct AuthorizedUserKey {
+    pub client_id: String,
+    pub client_secret: String,
+    pub refresh_token: String,
+    pub quota_project_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    token_type: String,
+    expires_in: i64,
+}
+
+// percent-encodes a value for an application/x-www-form-urlencoded body
+fn form_encode(value: &str) -> String {
+    value.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' |
+            b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+// exchanges the refresh token of a user for access tokens
+pub struct AuthorizedUserAccess {
+    key: AuthorizedUserKey,
+    client: Client,
+    token: Option<Token>,
+}
+
+impl AuthorizedUserAccess {
+    pub fn new(key: AuthorizedUserKey) -> Self {
+        Self {
+            key,
+            client: Client::default(),
+            token: None,
+        }
+    }
+    fn refresh(&mut self) -> Result<Token, Box<dyn Error>> {
+        let body = format!(
+            "grant_type=refresh_token&client_id={}&client_secret={}&refresh_token={}",
+            form_encode(&self.key.client_id),
+            form_encode(&self.key.client_secret),
+            form_encode(&self.key.refresh_token));
+        let mut response = self.client.0.post(TOKEN_URI)
+            .header(hyper::header::ContentType::form_url_encoded())
+            .body(body.as_str())
+            .send()?;
+        let mut content = String::new();
+        response.read_to_string(&mut content)?;
+        if !response.status.is_success() {
+            return Err(format!("Failed to refresh access token ({}): {}",
+                               response.status, content).into());
+        }
+        let response: TokenResponse = json::from_str(&content)?;
+        let mut token = Token {
+            access_token: response.access_token,
+            refresh_token: self.key.refresh_token.clone(),
+            token_type: response.token_type,
+            expires_in: Some(response.expires_in),
+            expires_in_timestamp: None,
+        };
+        token.set_expiry_absolute();
+        Ok(token)
+    }
+}
+
+impl GetToken for AuthorizedUserAccess {
+    fn token<'b, I, T>(&mut self, _scopes: I) -> Result<Token, Box<dyn Error>>
+    where
+        T: AsRef<str> + Ord + 'b,
+        I: IntoIterator<Item = &'b T>,
+    {
+        match &self.token {
+            Some(token) if !token.expired() => Ok(token.clone()),
+            _ => {
+                let token = self.refresh()?;
+                self.token = Some(token.clone());
+                Ok(token)
+            },
+        }
+    }
+
+    fn api_key(&mut self) -> Option<String> {
+        None
+    }
+}
+
+// reads a service account key or authorized user credentials file
+pub fn from_credentials_file<P: AsRef<Path>>(path: P) -> Result<FirestoreAccess, DatabaseError> {
+    let path = path.as_ref();
+    let content = read_credentials_file(path)?;
+    let invalid = |e: json::Error| auth_error(
+        format!("Invalid credentials file '{}': {}", path.display(), e));
+    let value: json::Value = json::from_str(&content).map_err(invalid)?;
+    match value["type"].as_str() {
+        Some("service_account") => {
+            let key: ServiceAccountKey = json::from_value(value).map_err(invalid)?;
+            FirestoreAccess::try_from(key)
+        },
+        Some("authorized_user") => {
+            let key: AuthorizedUserKey = json::from_value(value).map_err(invalid)?;
+            let project_id = project_from_env()
+                .or_else(|| key.quota_project_id.clone())
+                .ok_or_else(|| auth_error(format!(
+                    "No project for user credentials '{}'. \
+                     Please set GOOGLE_CLOUD_PROJECT environment variable!",
+                    path.display())))?;
+            Ok(FirestoreAccess {
+                project_id,
+                access: Credentials::AuthorizedUser(AuthorizedUserAccess::new(key)),
+            })
+        },
+        Some(other) => Err(auth_error(format!(
+            "Unsupported credentials type '{}' in '{}'", other, path.display()))),
+        None => Err(auth_error(format!(
+            "No credentials type in '{}'", path.display()))),
+    }
+}
+
+// a credentials file at an explicit path, which must exist
+pub struct KeyFile(pub PathBuf);
+
+impl CredentialProvider for KeyFile {
+    fn credentials(&self) -> Result<Option<FirestoreAccess>, DatabaseError> {
+        from_credentials_file(&self.0).map(Some)
+    }
+}
+
+// the credentials file at GOOGLE_APPLICATION_CREDENTIALS
+pub struct EnvKeyFile;
+
+impl CredentialProvider for EnvKeyFile {
+    fn credentials(&self) -> Result<Option<FirestoreAccess>, DatabaseError> {
+        match env::var("GOOGLE_APPLICATION_CREDENTIALS") {
+            Ok(path) if !path.is_empty() => from_credentials_file(path).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+// the credentials file written by `gcloud auth application-default login`
+pub struct WellKnownFile;
+
+impl WellKnownFile {
+    pub fn path() -> Option<PathBuf> {
+        let config = match env::var_os("CLOUDSDK_CONFIG") {
+            Some(dir) => PathBuf::from(dir),
+            None if cfg!(windows) => PathBuf::from(env::var_os("APPDATA")?).join("gcloud"),
+            None => PathBuf::from(env::var_os("HOME")?).join(".config").join("gcloud"),
+        };
+        Some(config.join("application_default_credentials.json"))
+    }
+}
+
+impl CredentialProvider for WellKnownFile {
+    fn credentials(&self) -> Result<Option<FirestoreAccess>, DatabaseError> {
+        match Self::path() {
+            Some(path) if path.is_file() => from_credentials_file(path).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+// an access token in GOOGLE_OAUTH_ACCESS_TOKEN, for the
+// project in GOOGLE_CLOUD_PROJECT or GCLOUD_PROJECT
+pub struct EnvAccessToken;
+
+impl CredentialProvider for EnvAccessToken {
+    fn credentials(&self) -> Result<Option<FirestoreAccess>, DatabaseError> {
+        let token = match env::var("GOOGLE_OAUTH_ACCESS_TOKEN") {
+            Ok(token) if !token.is_empty() => token,
+            _ => return Ok(None),
+        };
+        let project_id = project_from_env().ok_or_else(|| auth_error(
+                "GOOGLE_OAUTH_ACCESS_TOKEN is set, but no project. \
+                 Please set GOOGLE_CLOUD_PROJECT environment variable!"))?;
+        Ok(Some(FirestoreAccess {
+            project_id,
+            access: Credentials::AccessToken(token),
+        }))
+    }
+}
+
+// no authentication, if FIRESTORE_EMULATOR_HOST is set
+pub struct Emulator;
+
+impl CredentialProvider for Emulator {
+    fn credentials(&self) -> Result<Option<FirestoreAccess>, DatabaseError> {
+        Ok(emulator_host().map(|_| FirestoreAccess::emulator()))
+    }
+}
+
+// tries each provider in order and uses the first credentials found
+pub struct ProviderChain {
+    providers: Vec<Box<dyn CredentialProvider + Send + Sync>>,
+}
+
+impl ProviderChain {
+    pub fn new() -> Self {
+        Self {
+            providers: Vec::new(),
+        }
+    }
+    pub fn with<P: CredentialProvider + Send + Sync + 'static>(mut self, provider: P) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+    // the default chain, starting with an explicit credentials file
+    pub fn with_key_file<P: Into<PathBuf>>(path: P) -> Self {
+        let mut chain = Self::new().with(KeyFile(path.into()));
+        chain.providers.extend(Self::default().providers);
+        chain
+    }
+    pub fn resolve(&self) -> Result<FirestoreAccess, DatabaseError> {
+        for provider in &self.providers {
+            if let Some(access) = provider.credentials()? {
+                return Ok(access);
+            }
+        }
+        Err(auth_error(
+            "No credentials found. Please set FIRESTORE_EMULATOR_HOST, \
+             GOOGLE_APPLICATION_CREDENTIALS or GOOGLE_OAUTH_ACCESS_TOKEN \
+             environment variable, or run `gcloud auth application-default login`!"))
+    }
+}
+
+impl Default for ProviderChain {
+    // the emulator, GOOGLE_APPLICATION_CREDENTIALS, the well-known
+    // gcloud file and then an access token from the environment.
+    // The emulator comes first on purpose: when FIRESTORE_EMULATOR_HOST
+    // is set no credentials are read at all, so tests never reach a
+    // real project. Only an explicit key file (with_key_file) goes first
+    fn default() -> Self {
+        Self::new()
+            .with(Emulator)
+            .with(EnvKeyFile)
+            .with(WellKnownFile)
+            .with(EnvAccessToken)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // a path in the temp directory unique to this test run
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("firestore_{}_{}", std::process::id(), name))
+    }
+    // a file which is removed when the test is done
+    struct TempFile(PathBuf);
+    impl TempFile {
+        fn new(name: &str, content: &str) -> Self {
+            let path = temp_path(name);
+            fs::write(&path, content).unwrap();
+            TempFile(path)
+        }
+    }
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn key_file() {
+        let missing = KeyFile(temp_path("missing_credentials.json"));
+        assert_eq!(missing.credentials().err().unwrap().kind(), DatabaseErrorKind::Auth);
+
+        let file = TempFile::new("invalid_credentials.json", "{ not json");
+        let invalid = KeyFile(file.0.clone());
+        assert_eq!(invalid.credentials().err().unwrap().kind(), DatabaseErrorKind::Auth);
+
+        let file = TempFile::new("no_project_credentials.json",
+                                 r#"{ "type": "service_account" }"#);
+        let no_project = KeyFile(file.0.clone());
+        assert_eq!(no_project.credentials().err().unwrap().kind(), DatabaseErrorKind::Auth);
+
+        let file = TempFile::new("service_account_credentials.json",
+                                 r#"{
+                                         "type": "service_account",
+                                         "project_id": "test-project",
+                                         "client_email": "test@test-project.iam.gserviceaccount.com",
+                                         "private_key": "",
+                                         "token_uri": "https://oauth2.googleapis.com/token"
+                                     }"#);
+        let key = KeyFile(file.0.clone());
+        let access = key.credentials().unwrap().unwrap();
+        assert_eq!(access.project_id, "test-project");
+    }
+    #[test]
+    fn chain() {
+        struct Missing;
+        impl CredentialProvider for Missing {
+            fn credentials(&self) -> Result<Option<FirestoreAccess>, DatabaseError> {
+                Ok(None)
+            }
+        }
+        let empty = ProviderChain::new().with(Missing);
+        assert_eq!(empty.resolve().err().unwrap().kind(), DatabaseErrorKind::Auth);
+
+        // an invalid provider is reported instead of skipped
+        let invalid = ProviderChain::new()
+            .with(KeyFile(temp_path("missing_chain_credentials.json")))
+            .with(Missing);
+        assert!(invalid.resolve().is_err());
+    }
+    #[test]
+    fn encoding() {
+        assert_eq!(form_encode("1//0a-b_c.d~"), "1%2F%2F0a-b_c.d~");
+    }
+}
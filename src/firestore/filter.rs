@@ -5,6 +5,7 @@ use crate::{
     query::filter::{
         Filter,
         FilterOp,
+        FilterExpr,
     },
 };
 use crate::firestore::query::{
@@ -16,58 +17,83 @@ use google_firestore::{
     CompositeFilter,
 };
 
-impl<T: Clone + Into<FieldValue>> Filter<T> for FirestoreQuery {
-    fn filter(self, field: &str, op: FilterOp<T>) -> Self {
+impl Filter for FirestoreQuery {
+    fn filter_expr(self, expr: FilterExpr) -> Self {
         Self {
-            filter: FilterDef(field, op.into()).into(),
+            filter: self.filter.and(expr),
             ..self
         }
     }
     fn and(self, other: Self) -> Self {
         Self {
-            filter: google_firestore::Filter{
-                composite_filter: Some(CompositeFilter {
-                    filters: Some(vec![self.filter, other.filter]),
-                    op: Some("AND".to_string())
-                }),
-                ..google_firestore::Filter::default()
-            },
+            filter: self.filter.and(other.filter),
             ..self
         }
     }
     fn or(self, other: Self) -> Self {
         Self {
-            filter: google_firestore::Filter{
-                composite_filter: Some(CompositeFilter {
-                    filters: Some(vec![self.filter, other.filter]),
-                    op: Some("OR".to_string())
-                }),
-                ..google_firestore::Filter::default()
-            },
+            filter: self.filter.or(other.filter),
             ..self
         }
     }
 }
+fn composite(filters: Vec<FilterExpr>, op: &str) -> google_firestore::Filter {
+    let mut filters: Vec<google_firestore::Filter> = filters.into_iter()
+                                                            .map(|f| f.into())
+                                                            .collect();
+    match filters.len() {
+        // an empty filter matches all documents
+        0 => google_firestore::Filter::default(),
+        1 => filters.pop().unwrap(),
+        _ => google_firestore::Filter {
+            composite_filter: Some(CompositeFilter {
+                filters: Some(filters),
+                op: Some(op.to_string())
+            }),
+            ..google_firestore::Filter::default()
+        },
+    }
+}
+impl From<FilterExpr> for google_firestore::Filter {
+    fn from(expr: FilterExpr) -> Self {
+        match expr {
+            FilterExpr::Field(field, op) => FilterDef(&field, op).into(),
+            FilterExpr::And(filters) => composite(filters, "AND"),
+            FilterExpr::Or(filters) => composite(filters, "OR"),
+        }
+    }
+}
 #[derive(Clone)]
 struct FilterDef<'a, T: Into<FieldValue>>(&'a str, FilterOp::<T>);
-struct BinaryDef<'a, T: Into<FieldValue>>(&'a str, FilterOp::<T>, T);
+struct BinaryDef<'a, T: Into<FieldValue>>(&'a str, FilterOp::<T>, FieldValue);
 struct UnaryDef<'a, T: Into<FieldValue>>(&'a str, FilterOp::<T>);
 
 impl<'a, T: Clone + Into<FieldValue>> Into<google_firestore::Filter> for FilterDef::<'a, T> {
     fn into(self) -> google_firestore::Filter {
+        let binary = |value: FieldValue| google_firestore::Filter {
+            field_filter: Some(BinaryDef(self.0, self.1.clone(), value).into()),
+            ..google_firestore::Filter::default()
+        };
         match self.1.clone() {
             FilterOp::<T>::EQUAL(v) |
+                FilterOp::<T>::NOT_EQUAL(v) |
                 FilterOp::<T>::LESS_THAN(v) |
                 FilterOp::<T>::LESS_THAN_OR_EQUAL(v) |
                 FilterOp::<T>::GREATER_THAN(v) |
                 FilterOp::<T>::GREATER_THAN_OR_EQUAL(v) |
                 FilterOp::<T>::ARRAY_CONTAINS(v)
-                => google_firestore::Filter {
-                field_filter: Some(BinaryDef(self.0, self.1, v).into()),
-                ..google_firestore::Filter::default()
-            },
+                => binary(v.into()),
+            // operators on lists take an array value
+            FilterOp::<T>::IN(values) |
+                FilterOp::<T>::NOT_IN(values) |
+                FilterOp::<T>::ARRAY_CONTAINS_ANY(values)
+                => binary(FieldValue::from(values.into_iter()
+                                                 .map(|v| v.into())
+                                                 .collect::<Vec<FieldValue>>())),
             FilterOp::<T>::IS_NULL |
-            FilterOp::<T>::IS_NAN => google_firestore::Filter {
+            FilterOp::<T>::IS_NOT_NULL |
+            FilterOp::<T>::IS_NAN |
+            FilterOp::<T>::IS_NOT_NAN => google_firestore::Filter {
                 unary_filter: Some(UnaryDef(self.0, self.1).into()),
                 ..google_firestore::Filter::default()
             }
@@ -91,7 +117,7 @@ impl<'a, T: Into<FieldValue>> Into<google_firestore::FieldFilter> for BinaryDef:
                 field_path: Some(self.0.to_string())
             }),
             op: Some(self.1.to_string()),
-            value: Some(self.2.into().0),
+            value: Some(self.2.0),
         }
     }
 }
@@ -99,13 +125,19 @@ impl<T: Into<FieldValue>> ToString for FilterOp::<T> {
     fn to_string(&self) -> String {
         match self {
             FilterOp::EQUAL(_) => "EQUAL",
+            FilterOp::NOT_EQUAL(_) => "NOT_EQUAL",
             FilterOp::LESS_THAN(_) => "LESS_THAN",
             FilterOp::LESS_THAN_OR_EQUAL(_) => "LESS_THAN_OR_EQUAL",
             FilterOp::GREATER_THAN(_) => "GREATER_THAN",
             FilterOp::GREATER_THAN_OR_EQUAL(_) => "GREATER_THAN_OR_EQUAL",
             FilterOp::ARRAY_CONTAINS(_) => "ARRAY_CONTAINS",
+            FilterOp::IN(_) => "IN",
+            FilterOp::NOT_IN(_) => "NOT_IN",
+            FilterOp::ARRAY_CONTAINS_ANY(_) => "ARRAY_CONTAINS_ANY",
             FilterOp::IS_NULL => "IS_NULL",
+            FilterOp::IS_NOT_NULL => "IS_NOT_NULL",
             FilterOp::IS_NAN => "IS_NAN",
+            FilterOp::IS_NOT_NAN => "IS_NOT_NAN",
         }.into()
     }
 }
@@ -1,58 +1,153 @@
-use crate::client::{Client};
 use crate::query::{Query};
-use crate::document::{Document};
+use crate::document::{Document, FieldPath};
 use crate::error::*;
+use crate::write::{Write, Precondition};
 
 pub mod collection;
 pub mod query;
 pub mod filter;
 pub mod access;
+pub mod credentials;
+pub mod write;
+pub mod transaction;
+pub mod aggregation;
+mod pool;
 
 use access::{
     FirestoreAccess,
+    Credentials,
 };
-use std::sync::{Arc, Mutex};
+use pool::{HubPool, PooledHub};
+use credentials::{ProviderChain};
+use crate::database::{DatabaseFuture, DocumentStream, Lookup};
+use crate::list::{ListOptions};
+use crate::id::{IdGenerator, SharedIds};
+use std::collections::HashMap;
+use crate::blocking::blocking;
+use std::sync::{Arc};
 
-use actix_web::{
-    web::{
-        block,
-    },
-};
-use futures::future::{
-    Future,
+use futures::{
+    future,
+    stream::{self, TryStreamExt},
 };
 
+// a handle to a Firestore database. Clones share the same
+// connections, so handles can be passed around by value
+#[derive(Clone)]
 pub struct Firestore
 {
-    pub(crate) db: Arc<Mutex<google_firestore::Firestore<Client, FirestoreAccess>>>,
+    db: Arc<HubPool>,
     project_id: String,
+    ids: SharedIds,
+}
+
+// replaces the scheme and host of a Google API url
+// with plain HTTP to the given emulator host
+fn emulator_url(url: &str, host: &str) -> String {
+    let path = url.splitn(4, "/").nth(3).unwrap_or("");
+    format!("http://{}/{}", host, path)
 }
 
 impl Firestore {
-    pub(crate) fn db(&self) -> std::sync::MutexGuard<'_, google_firestore::Firestore<Client, FirestoreAccess>> {
-        self.db.lock().unwrap()
+    // connects with the first credentials found by the default ProviderChain
+    pub fn from_env() -> Result<Self, DatabaseError> {
+        Self::from_credentials(&ProviderChain::default())
+    }
+    pub fn from_credentials(chain: &ProviderChain) -> Result<Self, DatabaseError> {
+        chain.resolve().map(Self::new)
+    }
+    // connects to the emulator at host (e.g. "localhost:8080")
+    // instead of the Firestore server
+    pub fn with_emulator(project_id: &str, host: &str) -> Self {
+        info!("Using Firestore emulator at '{}'", host);
+        let access = FirestoreAccess {
+            project_id: project_id.to_string(),
+            access: Credentials::Emulator,
+        };
+        Firestore {
+            project_id: project_id.to_string(),
+            db: Arc::new(HubPool::new(access, Some(host.to_string()))),
+            ids: SharedIds::default(),
+        }
+    }
+    // name documents created without a name with ids of the generator
+    pub fn with_id_generator<G: IdGenerator + 'static>(self, ids: G) -> Self {
+        Self {
+            ids: SharedIds::new(ids),
+            ..self
+        }
+    }
+    // reads a single page of documents and the token of the next page
+    fn list_page(
+        &self,
+        path: &str,
+        collection_id: &str,
+        options: &ListOptions,
+        page_token: &str,
+        ) -> Result<(Vec<Document>, Option<String>), DatabaseError> {
+        let db = self.db();
+        let mut call = db.projects()
+            .databases_documents_list(path, collection_id);
+        if let Some(page_size) = options.page_size {
+            call = call.page_size(page_size as i32);
+        }
+        if let Some(order_by) = options.order_by_param() {
+            call = call.order_by(&order_by);
+        }
+        if options.show_missing {
+            call = call.show_missing(true);
+        }
+        for field in options.mask.iter().flatten() {
+            call = call.add_mask_field_paths(field.as_str());
+        }
+        if !page_token.is_empty() {
+            call = call.page_token(page_token);
+        }
+        let (_r, response) = call.doit()?;
+        Ok((response.documents
+                    .unwrap_or(Vec::new())
+                    .into_iter()
+                    .map(|d| match &options.mask {
+                        Some(mask) => Document::from(d).projected(mask.clone()),
+                        None => Document::from(d),
+                    })
+                    .collect(),
+            response.next_page_token
+                    .filter(|token| !token.is_empty())))
+    }
+    pub fn project_id(&self) -> &str {
+        &self.project_id
+    }
+    // a hub to make a request with, requests
+    // with different hubs can run in parallel
+    pub(crate) fn db(&self) -> PooledHub<'_> {
+        self.db.get()
     }
 }
 use super::{
     collection::Collection,
     database::Database,
 };
-impl Database<'static> for Firestore
+impl<'a> Database<'a> for Firestore
 {
     type Access = FirestoreAccess;
-    type Collection = collection::FirestoreCollection<'static>;
+    type Collection = collection::FirestoreCollection<'a>;
     type Query = query::FirestoreQuery;
+    type Transaction = transaction::FirestoreTransaction;
 
+    // uses the emulator if FIRESTORE_EMULATOR_HOST is set
     fn new(access: Self::Access) -> Self {
-        Firestore {
-            project_id: access.project_id.clone(),
-            db: Arc::new(Mutex::new(
-                    google_firestore::Firestore::new(Client::default(), access)
-                    )),
+        match access::emulator_host() {
+            Some(host) => Self::with_emulator(&access.project_id, &host),
+            None => Firestore {
+                project_id: access.project_id.clone(),
+                db: Arc::new(HubPool::new(access, None)),
+                ids: SharedIds::default(),
+            },
         }
     }
 
-    fn get_path(&'static self) -> String {
+    fn get_path(&'a self) -> String {
         format!(
             "projects/{}/databases/(default)/documents",
             self.project_id
@@ -60,100 +155,321 @@ impl Database<'static> for Firestore
     }
 
     fn collection<T: ToString>(
-        &'static self,
+        &'a self,
         collection_id: T,
         ) -> Self::Collection {
-        Self::Collection::new(&self, collection_id)
+        Self::Collection::new(self, collection_id)
     }
 
-    fn query(&'static self) -> Self::Query
+    fn query(&'a self) -> Self::Query
     {
-        Self::Query::new()
+        Self::Query::new().database(self)
     }
     fn create_document<T: ToString>(
-        &'static self,
+        &'a self,
         collection_id: T,
         document: Document
-        ) -> Box<dyn Future<Item=String, Error=DatabaseError> + Send> {
+        ) -> DatabaseFuture<String> {
+        let document = self.with_id(document);
+        let document_id = document.name().to_string();
         let doc = google_firestore::Document {
             name: None,
-            ..document.clone().into()
+            ..document.into()
         };
         let collection_id = collection_id.to_string();
         let path = self.get_path().clone();
-        Box::new(block(move || {
-            self.db()
+        let firestore = self.clone();
+        blocking(move || {
+            firestore.db()
                 .projects()
                 .databases_documents_create_document(
-                    doc.clone(),
+                    doc,
                     &path,
-                    &collection_id.to_string())
-                .document_id(&document.name()).doit()
-                .map(|(_r, _d)| document.name().to_string())
+                    &collection_id)
+                .document_id(&document_id).doit()
+                .map(|(_r, d)| d.name.unwrap_or_else(|| format!("{}/{}/{}",
+                                                                 path,
+                                                                 collection_id,
+                                                                 document_id)))
                 .map_err(|e| DatabaseError::from(e))
         })
-        .map_err(|e| DatabaseError::from(e))
-                )
     }
-    fn get_document<A: ToString, B: ToString>(
-        &'static self,
+    fn generate_id(&'a self) -> String {
+        self.ids.generate()
+    }
+    fn get_document_masked<A: ToString, B: ToString>(
+        &'a self,
         collection_id: A,
         document_id: B,
-        ) -> Box<dyn Future<Item=Document, Error=DatabaseError> + Send> {
+        mask: Option<Vec<FieldPath>>,
+        ) -> DatabaseFuture<Document> {
         let path = self.collection_path(collection_id);
         let document_id = document_id.to_string();
-        Box::new(block(move ||
-                       self.db()
-                       .projects()
-                       .databases_documents_get(&format!("{}/{}",
-                                                         path,
-                                                         &document_id)).doit()
-                       .map(|(_r, d)| Document::from(d))
-                       .map_err(|e| DatabaseError::from(e))
-                      )
-                       .map_err(|e| DatabaseError::from(e))
-                )
+        let firestore = self.clone();
+        blocking(move || {
+            let db = firestore.db();
+            let path = format!("{}/{}", path, &document_id);
+            let mut call = db.projects()
+                .databases_documents_get(&path);
+            for field in mask.iter().flatten() {
+                call = call.add_mask_field_paths(field.as_str());
+            }
+            call.doit()
+                .map(|(_r, d)| match mask {
+                    Some(mask) => Document::from(d).projected(mask),
+                    None => Document::from(d),
+                })
+                .map_err(|e| DatabaseError::from(e))
+        })
+    }
+    fn get_documents_by_id<T: ToString>(
+        &'a self,
+        paths: &[T],
+        ) -> DatabaseFuture<Vec<Lookup>> {
+        let paths: Vec<String> = paths.iter()
+            .map(|path| format!("{}/{}", self.get_path(), path.to_string()))
+            .collect();
+        // Firestore rejects a batchGet without documents
+        if paths.is_empty() {
+            return Box::pin(future::ok(Vec::new()));
+        }
+        let mut documents = paths.clone();
+        documents.sort();
+        documents.dedup();
+        let request = google_firestore::BatchGetDocumentsRequest {
+            documents: Some(documents),
+            ..google_firestore::BatchGetDocumentsRequest::default()
+        };
+        let database = self.database_name();
+        let firestore = self.clone();
+        blocking(move || {
+            let (_r, responses) = firestore.db()
+                .projects()
+                .databases_documents_batch_get(request, &database)
+                .doit()?;
+            // the responses come in any order
+            let found: HashMap<String, Document> = responses.into_iter()
+                .filter_map(|response| response.found)
+                .map(Document::from)
+                .map(|document| (document.id().to_string(), document))
+                .collect();
+            Ok(paths.into_iter()
+                    .map(|path| match found.get(&path) {
+                        Some(document) => Lookup::Found(document.clone()),
+                        None => Lookup::Missing(path),
+                    })
+                    .collect())
+        })
+    }
+    fn update_document_if<A: ToString, B: ToString>(
+        &'a self,
+        collection_id: A,
+        document_id: B,
+        document: Document,
+        mask: Option<Vec<FieldPath>>,
+        precondition: Precondition,
+        ) -> DatabaseFuture<Document> {
+        let path = format!("{}/{}",
+                           self.collection_path(collection_id),
+                           document_id.to_string());
+        let doc = google_firestore::Document {
+            name: None,
+            create_time: None,
+            update_time: None,
+            ..document.into()
+        };
+        let firestore = self.clone();
+        blocking(move || {
+            let db = firestore.db();
+            let mut call = db.projects()
+                .databases_documents_patch(doc, &path);
+            call = match &precondition {
+                Precondition::Exists(exists) => call.current_document_exists(*exists),
+                Precondition::UpdateTime(time) => call.current_document_update_time(time),
+            };
+            for field in mask.iter().flatten() {
+                call = call.add_update_mask_field_paths(field.as_str());
+            }
+            call.doit()
+                .map(|(_r, d)| Document::from(d))
+                .map_err(|e| write::precondition_error(e.into(), Some(&precondition).into_iter()))
+        })
     }
-    fn delete_document<A: ToString, B: ToString>(
-        &'static self,
+    fn delete_document_if<A: ToString, B: ToString>(
+        &'a self,
         collection_id: A,
         document_id: B,
-        ) -> Box<dyn Future<Item=(), Error=DatabaseError> + Send> {
+        precondition: Option<Precondition>,
+        ) -> DatabaseFuture<()> {
         let path = self.collection_path(collection_id);
         let document_id = document_id.to_string();
-        Box::new(block(move ||
-                       self.db()
-                       .projects()
-                       .databases_documents_delete(&format!("{}/{}",
-                                                            path,
-                                                            &document_id)).doit()
-                       .map(|(_r, _)| ())
-                       .map_err(|e| DatabaseError::from(e))
-                      )
-                       .map_err(|e| DatabaseError::from(e))
-                )
+        let firestore = self.clone();
+        blocking(move || {
+            let db = firestore.db();
+            let path = format!("{}/{}", path, &document_id);
+            let mut call = db.projects()
+                .databases_documents_delete(&path);
+            call = match &precondition {
+                Some(Precondition::Exists(exists)) => call.current_document_exists(*exists),
+                Some(Precondition::UpdateTime(time)) => call.current_document_update_time(time),
+                None => call,
+            };
+            call.doit()
+                .map(|(_r, _)| ())
+                .map_err(|e| write::precondition_error(e.into(), precondition.iter()))
+        })
     }
-    fn get_documents<A: ToString>(
-        &'static self,
+    fn list_documents<A: ToString>(
+        &'a self,
         collection_id: A,
-        ) -> Box<dyn Future<Item=Vec<Document>, Error=DatabaseError>> {
+        options: ListOptions,
+        ) -> DocumentStream {
+        if let Err(e) = options.check() {
+            return Box::pin(stream::iter(vec![Err(e)]));
+        }
         let collection_id = collection_id.to_string();
         let path = self.get_path();
-        Box::new(block(move ||
-                       self.db()
-                       .projects()
-                       .databases_documents_list(
-                           &path,
-                           &collection_id).doit()
-                       .map_err(|e| DatabaseError::from(e))
-                       .map(|(_r, res)|
-                            res.documents
-                            .unwrap_or(Vec::new())
-                            .iter()
-                            .map(|doc| doc.clone().into())
-                            .collect()
-                           ))
-                       .map_err(|e| DatabaseError::from(e))
+        let firestore = self.clone();
+        // the state is the token of the next page, None after the last page
+        let pages = stream::try_unfold(Some(String::new()), move |page_token| {
+            let firestore = firestore.clone();
+            let path = path.clone();
+            let collection_id = collection_id.clone();
+            let options = options.clone();
+            async move {
+                let page_token = match page_token {
+                    Some(page_token) => page_token,
+                    None => return Ok(None),
+                };
+                let (documents, next_page_token) = blocking(move ||
+                    firestore.list_page(&path, &collection_id, &options, &page_token)
+                ).await?;
+                Ok(Some((stream::iter(documents.into_iter().map(Ok)), next_page_token)))
+            }
+        });
+        Box::pin(pages.try_flatten())
+    }
+    fn commit(
+        &'a self,
+        writes: Vec<Write>,
+        ) -> DatabaseFuture<()> {
+        if let Err(e) = writes.iter().try_for_each(Write::check_document_id) {
+            return Box::pin(future::err(e));
+        }
+        let preconditions: Vec<Precondition> = writes.iter()
+            .filter_map(|w| w.precondition().cloned())
+            .collect();
+        let request = google_firestore::CommitRequest {
+            writes: Some(writes.into_iter()
+                               .flat_map(|w| self.firestore_writes(w))
+                               .collect()),
+            ..google_firestore::CommitRequest::default()
+        };
+        let database = self.database_name();
+        let firestore = self.clone();
+        blocking(move ||
+                 firestore.db()
+                 .projects()
+                 .databases_documents_commit(request, &database).doit()
+                 .map(|(_r, _)| ())
+                 .map_err(|e| write::precondition_error(e.into(), preconditions.iter()))
                 )
     }
+    fn begin_transaction(&'a self) -> Result<Self::Transaction, DatabaseError> {
+        self.db()
+            .projects()
+            .databases_documents_begin_transaction(
+                google_firestore::BeginTransactionRequest::default(),
+                &self.database_name())
+            .doit()
+            .map(|(_r, res)| transaction::FirestoreTransaction::new(
+                    self.clone(),
+                    res.transaction.unwrap_or_default()))
+            .map_err(|e| DatabaseError::from(e))
+    }
+    fn commit_transaction(
+        &'a self,
+        transaction: Self::Transaction,
+        ) -> Result<(), DatabaseError> {
+        transaction.writes.iter().try_for_each(Write::check_document_id)?;
+        let preconditions: Vec<Precondition> = transaction.writes
+            .iter()
+            .filter_map(|w| w.precondition().cloned())
+            .collect();
+        let request = google_firestore::CommitRequest {
+            writes: Some(transaction.writes
+                                    .into_iter()
+                                    .flat_map(|w| self.firestore_writes(w))
+                                    .collect()),
+            transaction: Some(transaction.id),
+            ..google_firestore::CommitRequest::default()
+        };
+        self.db()
+            .projects()
+            .databases_documents_commit(request, &self.database_name())
+            .doit()
+            .map(|(_r, _)| ())
+            .map_err(|e| write::precondition_error(e.into(), preconditions.iter()))
+    }
+    fn rollback_transaction(
+        &'a self,
+        transaction: Self::Transaction,
+        ) -> Result<(), DatabaseError> {
+        let request = google_firestore::RollbackRequest {
+            transaction: Some(transaction.id),
+            ..google_firestore::RollbackRequest::default()
+        };
+        self.db()
+            .projects()
+            .databases_documents_rollback(request, &self.database_name())
+            .doit()
+            .map(|(_r, _)| ())
+            .map_err(|e| DatabaseError::from(e))
+    }
+    fn run_transaction<T, F>(
+        &'a self,
+        f: F,
+        ) -> DatabaseFuture<T>
+        where T: Send + 'static,
+              F: FnMut(&mut Self::Transaction) -> Result<T, DatabaseError> + Send + 'static
+    {
+        let firestore = self.clone();
+        blocking(move || crate::transaction::run(&firestore, f))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use query::FirestoreQuery;
+    use futures::executor::block_on;
+    #[test]
+    fn handles() {
+        let a = Firestore::with_emulator("project-a", "localhost:8080");
+        let b = Firestore::with_emulator("project-b", "localhost:8080");
+        assert_eq!(a.get_path(), "projects/project-a/databases/(default)/documents");
+        assert_eq!(b.collection("test").get_path(),
+                   "projects/project-b/databases/(default)/documents/test");
+        // queries run on the handle which built them
+        let query = b.query();
+        assert_eq!(query.database.as_ref().map(|db| db.project_id()), Some("project-b"));
+        let error = block_on(<FirestoreQuery as Query<'_, Firestore>>::new().run()).unwrap_err();
+        assert_eq!(error.kind(), DatabaseErrorKind::InvalidArgument);
+    }
+    #[test]
+    fn no_documents_by_id() {
+        // answered without a request, so no emulator has to run
+        let database = Firestore::with_emulator("project", "localhost:1");
+        let paths: &[&str] = &[];
+        assert!(block_on(database.get_documents_by_id(paths)).unwrap().is_empty());
+    }
+    #[test]
+    fn emulator_url() {
+        assert_eq!(super::emulator_url("https://firestore.googleapis.com/v1beta1/",
+                                       "localhost:8080"),
+                   "http://localhost:8080/v1beta1/");
+        assert_eq!(super::emulator_url("https://firestore.googleapis.com/",
+                                       "localhost:8080"),
+                   "http://localhost:8080/");
+    }
 }
@@ -0,0 +1,160 @@
+use crate::client::{Client};
+use crate::error::{DatabaseError, DatabaseErrorKind};
+use crate::firestore::{
+    emulator_url,
+    access::{
+        FirestoreAccess,
+        SharedAccess,
+    },
+};
+use oauth2::{GetToken};
+use hyper::header::{Authorization, Bearer, ContentType};
+use std::io::Read;
+use std::ops::{Deref};
+use std::sync::{Mutex};
+
+pub(crate) type Hub = google_firestore::Firestore<Client, SharedAccess>;
+
+// how many unused hubs are kept for later requests
+const MAX_IDLE: usize = 32;
+const SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+// the REST API for requests to methods the hubs do not have
+const BASE_URL: &str = "https://firestore.googleapis.com/v1/";
+
+// A hub can only make one request at a time, so HubPool
+// lends each request its own hub. Hubs are created when all
+// are in use and returned to the pool after the request.
+// All hubs share the same credentials and token cache
+pub(crate) struct HubPool {
+    access: SharedAccess,
+    // the emulator host to send requests to, if any
+    emulator: Option<String>,
+    idle: Mutex<Vec<Hub>>,
+    // for requests to methods the hubs do not have
+    client: Client,
+    base_url: String,
+}
+
+impl HubPool {
+    pub(crate) fn new(access: FirestoreAccess, emulator: Option<String>) -> Self {
+        let base_url = match &emulator {
+            Some(host) => emulator_url(BASE_URL, host),
+            None => BASE_URL.to_string(),
+        };
+        Self {
+            access: SharedAccess::new(access),
+            emulator,
+            idle: Mutex::new(Vec::new()),
+            client: Client::default(),
+            base_url,
+        }
+    }
+    fn create(&self) -> Hub {
+        let mut hub = google_firestore::Firestore::new(Client::default(), self.access.clone());
+        if let Some(host) = &self.emulator {
+            let base_url = hub.base_url(String::new());
+            hub.base_url(emulator_url(&base_url, host));
+            let root_url = hub.root_url(String::new());
+            hub.root_url(emulator_url(&root_url, host));
+        }
+        hub
+    }
+    // borrow a hub until the returned guard is dropped
+    pub(crate) fn get(&self) -> PooledHub<'_> {
+        // the lock is only held to take a hub, not during requests
+        let hub = self.idle.lock().unwrap().pop();
+        PooledHub {
+            pool: self,
+            hub: Some(hub.unwrap_or_else(|| self.create())),
+        }
+    }
+    // posts a JSON request to a method of the API, like
+    // "projects/p/databases/(default)/documents:runAggregationQuery",
+    // with the credentials of the hubs
+    pub(crate) fn post(&self, method: &str, body: &json::Value) -> Result<json::Value, DatabaseError> {
+        let token = self.access.clone()
+            .token(&[SCOPE])
+            .map_err(|e| DatabaseError::new(DatabaseErrorKind::Auth, e))?;
+        let body = body.to_string();
+        let mut response = self.client.0.post(&format!("{}{}", self.base_url, method))
+            .header(Authorization(Bearer { token: token.access_token }))
+            .header(ContentType::json())
+            .body(body.as_str())
+            .send()
+            .map_err(|e| DatabaseError::new(DatabaseErrorKind::Transport, e))?;
+        let mut content = String::new();
+        response.read_to_string(&mut content)
+            .map_err(|e| DatabaseError::new(DatabaseErrorKind::Transport, e))?;
+        if !response.status.is_success() {
+            return Err(DatabaseError::new(error_kind(response.status.to_u16(), &content), content));
+        }
+        json::from_str(&content)
+            .map_err(|e| DatabaseError::new(DatabaseErrorKind::Decode, e))
+    }
+    #[cfg(test)]
+    pub(crate) fn idle(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+}
+
+// the kind of an error response, which need not be JSON,
+// e.g. when a proxy answers with 503
+fn error_kind(status: u16, content: &str) -> DatabaseErrorKind {
+    match json::from_str::<json::Value>(content)
+              .map(|value| DatabaseErrorKind::from_response(&value)) {
+        Ok(DatabaseErrorKind::Other) |
+        Err(_) => DatabaseErrorKind::from_http_status(status, content),
+        Ok(kind) => kind,
+    }
+}
+
+pub(crate) struct PooledHub<'a> {
+    pool: &'a HubPool,
+    hub: Option<Hub>,
+}
+
+impl<'a> Deref for PooledHub<'a> {
+    type Target = Hub;
+    fn deref(&self) -> &Hub {
+        self.hub.as_ref().unwrap()
+    }
+}
+
+impl<'a> Drop for PooledHub<'a> {
+    fn drop(&mut self) {
+        let mut idle = self.pool.idle.lock().unwrap();
+        if idle.len() < MAX_IDLE {
+            idle.extend(self.hub.take());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn reuse() {
+        let pool = HubPool::new(FirestoreAccess::emulator(), Some("localhost:8080".to_string()));
+        {
+            // concurrent requests use separate hubs
+            let _a = pool.get();
+            let _b = pool.get();
+            assert_eq!(pool.idle(), 0);
+        }
+        assert_eq!(pool.idle(), 2);
+        let _c = pool.get();
+        assert_eq!(pool.idle(), 1);
+    }
+    #[test]
+    fn base_url() {
+        let pool = HubPool::new(FirestoreAccess::emulator(), Some("localhost:8080".to_string()));
+        assert_eq!(pool.base_url, "http://localhost:8080/v1/");
+    }
+    #[test]
+    fn error_kinds() {
+        assert_eq!(error_kind(503, "<html>Service Unavailable</html>"), DatabaseErrorKind::Unavailable);
+        assert_eq!(error_kind(400, r#"{"error": {"code": 400, "status": "NOT_FOUND"}}"#),
+                   DatabaseErrorKind::NotFound);
+        assert_eq!(error_kind(500, ""), DatabaseErrorKind::Other);
+    }
+}
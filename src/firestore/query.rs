@@ -1,26 +1,45 @@
 use crate::{
-    database,
-    database::Database,
-    error::DatabaseError,
+    database::{Database, DatabaseFuture},
+    error::{DatabaseError, DatabaseErrorKind},
     Firestore,
+    blocking::blocking,
     document::{
         Document,
+        FieldValue,
+        FieldPath,
     },
-    query::ordering::{
-        Ordering,
+    query::{
+        eval,
+        cursor::Cursor,
+        filter::FilterExpr,
+        ordering::{
+            Ordering,
+        },
     },
 };
 
 use google_firestore::{
     RunQueryRequest,
     StructuredQuery,
+    Projection,
+    FieldReference,
 };
+#[derive(Clone)]
 pub struct FirestoreQuery {
+    pub(crate) database: Option<Firestore>,
     pub(crate) collections: Vec<CollectionSelector>,
-    pub(crate) filter: google_firestore::Filter,
+    pub(crate) filter: FilterExpr,
     pub(crate) orders: Vec<google_firestore::Order>,
+    // cursors with whether their position is before their values
+    pub(crate) start: Option<(Cursor, bool)>,
+    pub(crate) end: Option<(Cursor, bool)>,
     pub(crate) limit: u32,
     pub(crate) skip: u32,
+    // the fields to return, all fields if None
+    pub(crate) select: Option<Vec<FieldPath>>,
+    // the path of the document to query the collections
+    // of, relative to the database root. None for the root
+    pub(crate) parent: Option<String>,
 }
 
 // CollectionSelectors are used to select
@@ -32,6 +51,12 @@ impl CollectionSelector {
     pub fn set_all_descendants(&mut self, all_descendants: bool) {
         self.0.all_descendants = Some(all_descendants);
     }
+    pub fn collection_id(&self) -> Option<&str> {
+        self.0.collection_id.as_ref().map(|id| id.as_str())
+    }
+    pub fn all_descendants(&self) -> bool {
+        self.0.all_descendants.unwrap_or(false)
+    }
 }
 
 impl<T: ToString> From<T> for CollectionSelector {
@@ -55,14 +80,19 @@ impl Default for CollectionSelector {
     }
 }
 use crate::query::{Query};
-impl Query<'static, Firestore> for FirestoreQuery {
+impl<'a> Query<'a, Firestore> for FirestoreQuery {
     fn new() -> Self {
         FirestoreQuery {
+            database: None,
             collections: Vec::new(),
-            filter: google_firestore::Filter::default(),
+            filter: FilterExpr::default(),
             orders: Vec::new(),
+            start: None,
+            end: None,
             limit: 0,
             skip: 0,
+            select: None,
+            parent: None,
         }
     }
     fn collections(self, mut collections: Vec<CollectionSelector>) -> Self
@@ -103,16 +133,156 @@ impl Query<'static, Firestore> for FirestoreQuery {
             ..self
         }
     }
+    fn under(self, parent_document_path: &str) -> Self {
+        Self {
+            parent: Some(parent_document_path.trim_matches('/').to_string())
+                        .filter(|path| !path.is_empty()),
+            ..self
+        }
+    }
+    fn select(self, fields: &[&str]) -> Self {
+        Self {
+            select: Some(fields.iter()
+                               .map(|f| FieldPath::from(*f))
+                               .collect()),
+            ..self
+        }
+    }
+    fn start_at<C: Into<Cursor>>(self, cursor: C) -> Self {
+        Self {
+            start: Some((cursor.into(), true)),
+            ..self
+        }
+    }
+    fn start_after<C: Into<Cursor>>(self, cursor: C) -> Self {
+        Self {
+            start: Some((cursor.into(), false)),
+            ..self
+        }
+    }
+    fn end_at<C: Into<Cursor>>(self, cursor: C) -> Self {
+        Self {
+            end: Some((cursor.into(), false)),
+            ..self
+        }
+    }
+    fn end_before<C: Into<Cursor>>(self, cursor: C) -> Self {
+        Self {
+            end: Some((cursor.into(), true)),
+            ..self
+        }
+    }
+    fn cursor_values(&self, document: &Document) -> Vec<FieldValue> {
+        Cursor::from(document).values(&eval::effective_orders(&self.firestore_filter(), &self.orders))
+    }
 
-    fn run(self) -> Result<Vec<Document>, DatabaseError> {
-        let req = RunQueryRequest {
+    fn fingerprint(&self) -> u64 {
+        // json! sorts the keys of maps, so equal queries hash equally
+        let query = json::json!({
+            "parent": self.parent,
+            "from": self.collections.iter().map(|c| &c.0).collect::<Vec<_>>(),
+            "where": self.firestore_filter(),
+            "orderBy": eval::effective_orders(&self.firestore_filter(), &self.orders),
+        });
+        // FNV-1a, which unlike the std hashers is the same in every build
+        query.to_string()
+             .bytes()
+             .fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+    }
+
+    fn run(self) -> DatabaseFuture<Vec<Document>> {
+        blocking(move || self.run_request(self.request()))
+    }
+}
+
+impl FirestoreQuery {
+    // set the database to run the query on
+    pub fn database(self, database: &Firestore) -> Self {
+        Self {
+            database: Some(database.clone()),
+            ..self
+        }
+    }
+    // applies the query to local documents and returns
+    // what Firestore would return when running it
+    pub fn evaluate<I: IntoIterator<Item=Document>>(&self, documents: I) -> Vec<Document> {
+        let (start, end) = self.cursors();
+        let results = eval::evaluate(&self.firestore_filter(),
+                                     &self.orders,
+                                     start.as_ref(),
+                                     end.as_ref(),
+                                     self.limit,
+                                     self.skip,
+                                     documents);
+        match self.projection() {
+            Some(fields) => results.iter()
+                                   .map(|d| d.masked(&fields))
+                                   .collect(),
+            None => results,
+        }
+    }
+    // the selected fields and the fields the query is ordered by,
+    // so the results can be used as cursors of the query
+    fn projection(&self) -> Option<Vec<FieldPath>> {
+        let mut fields = self.select.clone()?;
+        for order in eval::effective_orders(&self.firestore_filter(), &self.orders) {
+            let path = match order.field.and_then(|f| f.field_path) {
+                Some(path) => FieldPath::from(path.as_str()),
+                None => continue,
+            };
+            if path.as_str() != "__name__" && !fields.contains(&path) {
+                fields.push(path);
+            }
+        }
+        Some(fields)
+    }
+    // the full path of the parent of the queried collections
+    pub(crate) fn parent_path(&self, database: &Firestore) -> String {
+        match &self.parent {
+            Some(parent) => format!("{}/{}", database.get_path(), parent),
+            None => database.get_path(),
+        }
+    }
+    fn firestore_filter(&self) -> google_firestore::Filter {
+        self.filter.clone().into()
+    }
+    // the orders to send with the query. Cursors refer to all
+    // orders Firestore applies, so the implicit ones are included
+    fn request_orders(&self) -> Vec<google_firestore::Order> {
+        if self.start.is_some() || self.end.is_some() {
+            eval::effective_orders(&self.firestore_filter(), &self.orders)
+        } else {
+            self.orders.clone()
+        }
+    }
+    fn cursors(&self) -> (Option<google_firestore::Cursor>, Option<google_firestore::Cursor>) {
+        let orders = self.request_orders();
+        (self.start.as_ref().map(|(cursor, before)| cursor.to_firestore(&orders, *before)),
+         self.end.as_ref().map(|(cursor, before)| cursor.to_firestore(&orders, *before)))
+    }
+    pub(crate) fn request(&self) -> RunQueryRequest {
+        let (start_at, end_at) = self.cursors();
+        RunQueryRequest {
             structured_query: Some(StructuredQuery {
                     from: Some(self.collections
                                    .iter()
                                    .map(|c| c.0.clone())
                                    .collect()),
-                    where_: Some(self.filter),
-                    order_by: Some(self.orders.clone()),
+                    where_: if self.filter.is_empty() {
+                                None
+                            } else {
+                                Some(self.firestore_filter())
+                            },
+                    select: self.projection().map(|fields| Projection {
+                        fields: Some(fields.iter()
+                                           .map(|f| FieldReference {
+                                               field_path: Some(f.as_str().to_string()),
+                                           })
+                                           .collect()),
+                    }),
+                    order_by: Some(self.request_orders()),
+                    start_at,
+                    end_at,
                     limit:  if self.limit == 0 {
                                 None
                             } else {
@@ -126,17 +296,33 @@ impl Query<'static, Firestore> for FirestoreQuery {
                     ..StructuredQuery::default()
                 }),
             ..RunQueryRequest::default()
-        };
-        let (_httpresponse, results) = database().db()
+        }
+    }
+    fn run_request(&self, req: RunQueryRequest) -> Result<Vec<Document>, DatabaseError> {
+        let database = self.database.as_ref().ok_or_else(|| DatabaseError::new(
+                DatabaseErrorKind::InvalidArgument,
+                "The query has no database to run on, create it with Firestore::query"))?;
+        let (_httpresponse, results) = database.db()
             .projects()
             .databases_documents_run_query(req,
-                                           &database().get_path())
+                                           &self.parent_path(database))
             .doit()?;
+        let projection = self.projection();
         Ok(results.iter()
                   .flat_map(|res| (*res).clone().document)
-                  .map(|d| Document::from(d))
+                  .map(|d| match &projection {
+                      Some(fields) => Document::from(d).projected(fields.clone()),
+                      None => Document::from(d),
+                  })
                   .collect())
     }
+    // run the query reading from a transaction
+    pub(crate) fn run_in_transaction(self, transaction: &str) -> Result<Vec<Document>, DatabaseError> {
+        self.run_request(RunQueryRequest {
+            transaction: Some(transaction.to_string()),
+            ..self.request()
+        })
+    }
 }
 
 #[cfg(test)]
@@ -154,10 +340,11 @@ mod tests {
         FieldValue,
         ArrayValue,
     };
+    use futures::executor::block_on;
     mod single_collection {
         use super::*;
         use crate::{
-            database,
+            try_database,
             database::Database,
             query::{
                 filter::{
@@ -167,6 +354,9 @@ mod tests {
             },
         };
 
+        fn database() -> &'static Firestore {
+            try_database().unwrap()
+        }
         fn test_collection() -> Vec<CollectionSelector> {
             vec![CollectionSelector::from("test")]
         }
@@ -175,23 +365,25 @@ mod tests {
             use super::*;
             #[test]
             fn is_null() {
-                let query = database().query()
-                              .collections(test_collection())
-                              .filter("test_null", FilterOp::<()>::IS_NULL);
-                let expected = vec![
-                            Document::builder()
-                                .name(&(database().get_path() + "/test/" + "IsNull"))
-                                .field("test_null", FieldValue::null_value())
-                                .build()
-                           ];
-                match query.run() {
-                    Ok(results) => {
-                        assert_eq!(results, expected);
-                    },
-                    Err(e) => {
-                        panic!("Failed to run query: {}", e);
+                block_on(async {
+                    let query = database().query()
+                                  .collections(test_collection())
+                                  .filter("test_null", FilterOp::<()>::IS_NULL);
+                    let expected = vec![
+                                Document::builder()
+                                    .name(&(database().get_path() + "/test/" + "IsNull"))
+                                    .field("test_null", FieldValue::null_value())
+                                    .build()
+                               ];
+                    match query.run().await {
+                        Ok(results) => {
+                            assert_eq!(results, expected);
+                        },
+                        Err(e) => {
+                            panic!("Failed to run query: {}", e);
+                        }
                     }
-                }
+                })
             }
             // TODO: Test IS_NAN
         }
@@ -200,146 +392,158 @@ mod tests {
             use super::*;
             #[test]
             fn equal() {
-                let query = database().query()
-                              .collections(test_collection())
-                              .filter("string", FilterOp::EQUAL("TestString"));
-                let expected = vec![
-                            Document::builder()
-                                .name(&(database().get_path() + "/test/" + "StringEquals"))
-                                .field("string", "TestString")
-                                .build()
-                           ];
-                match query.run() {
-                    Ok(results) => {
-                        assert_eq!(results, expected);
-                    },
-                    Err(e) => {
-                        panic!("Failed to run query: {}", e);
+                block_on(async {
+                    let query = database().query()
+                                  .collections(test_collection())
+                                  .filter("string", FilterOp::EQUAL("TestString"));
+                    let expected = vec![
+                                Document::builder()
+                                    .name(&(database().get_path() + "/test/" + "StringEquals"))
+                                    .field("string", "TestString")
+                                    .build()
+                               ];
+                    match query.run().await {
+                        Ok(results) => {
+                            assert_eq!(results, expected);
+                        },
+                        Err(e) => {
+                            panic!("Failed to run query: {}", e);
+                        }
                     }
-                }
+                })
             }
             #[test]
             fn less_than() {
-                let query = database().query()
-                              .collections(test_collection())
-                              .filter("integer", FilterOp::LESS_THAN(5));
-                let expected = vec![
-                            Document::builder()
-                                .name(&(database().get_path() + "/test/" + "LessThan"))
-                                .field("integer", 3)
-                                .build()
-                           ];
-                match query.run() {
-                    Ok(results) => {
-                        assert_eq!(results, expected);
-                    },
-                    Err(e) => {
-                        panic!("Failed to run query: {}", e);
+                block_on(async {
+                    let query = database().query()
+                                  .collections(test_collection())
+                                  .filter("integer", FilterOp::LESS_THAN(5));
+                    let expected = vec![
+                                Document::builder()
+                                    .name(&(database().get_path() + "/test/" + "LessThan"))
+                                    .field("integer", 3)
+                                    .build()
+                               ];
+                    match query.run().await {
+                        Ok(results) => {
+                            assert_eq!(results, expected);
+                        },
+                        Err(e) => {
+                            panic!("Failed to run query: {}", e);
+                        }
                     }
-                }
+                })
             }
             #[test]
             fn less_than_or_equal() {
-                let query = database().query()
-                              .collections(test_collection())
-                              .filter("integer", FilterOp::LESS_THAN_OR_EQUAL(5));
-                let expected = vec![
-                            Document::builder()
-                                .name(&(database().get_path() + "/test/" + "LessThan"))
-                                .field("integer", 3)
-                                .build(),
-                            Document::builder()
-                                .name(&(database().get_path() + "/test/" + "GreaterThanOrEqual"))
-                                .field("integer", 5)
-                                .build(),
-                            Document::builder()
-                                .name(&(database().get_path() + "/test/" + "LessThanOrEqual"))
-                                .field("integer", 5)
-                                .build(),
-                           ];
-                match query.run() {
-                    Ok(results) => {
-                        assert_eq!(results, expected);
-                    },
-                    Err(e) => {
-                        panic!("Failed to run query: {}", e);
+                block_on(async {
+                    let query = database().query()
+                                  .collections(test_collection())
+                                  .filter("integer", FilterOp::LESS_THAN_OR_EQUAL(5));
+                    let expected = vec![
+                                Document::builder()
+                                    .name(&(database().get_path() + "/test/" + "LessThan"))
+                                    .field("integer", 3)
+                                    .build(),
+                                Document::builder()
+                                    .name(&(database().get_path() + "/test/" + "GreaterThanOrEqual"))
+                                    .field("integer", 5)
+                                    .build(),
+                                Document::builder()
+                                    .name(&(database().get_path() + "/test/" + "LessThanOrEqual"))
+                                    .field("integer", 5)
+                                    .build(),
+                               ];
+                    match query.run().await {
+                        Ok(results) => {
+                            assert_eq!(results, expected);
+                        },
+                        Err(e) => {
+                            panic!("Failed to run query: {}", e);
+                        }
                     }
-                }
+                })
             }
             #[test]
             fn greater_than() {
-                let query = database().query()
-                              .collections(test_collection())
-                              .filter("integer", FilterOp::GREATER_THAN(5));
-                let name = database().get_path() + "/test/" + "GreaterThan";
-                let expected = vec![
-                            Document::builder()
-                                .name(&name)
-                                .field("integer", 6)
-                                .build(),
-                           ];
-                match query.run() {
-                    Ok(results) => {
-                        assert_eq!(results, expected);
-                    },
-                    Err(e) => {
-                        panic!("Failed to run query: {}", e);
+                block_on(async {
+                    let query = database().query()
+                                  .collections(test_collection())
+                                  .filter("integer", FilterOp::GREATER_THAN(5));
+                    let name = database().get_path() + "/test/" + "GreaterThan";
+                    let expected = vec![
+                                Document::builder()
+                                    .name(&name)
+                                    .field("integer", 6)
+                                    .build(),
+                               ];
+                    match query.run().await {
+                        Ok(results) => {
+                            assert_eq!(results, expected);
+                        },
+                        Err(e) => {
+                            panic!("Failed to run query: {}", e);
+                        }
                     }
-                }
+                })
             }
             #[test]
             fn greater_than_or_equal() {
-                let query = database().query()
-                              .collections(test_collection())
-                              .filter("integer", FilterOp::GREATER_THAN_OR_EQUAL(5));
-                let expected = vec![
-                            Document::builder()
-                                .name(&(database().get_path() + "/test/" + "GreaterThanOrEqual"))
-                                .field("integer", 5)
-                                .build(),
-                            Document::builder()
-                                .name(&(database().get_path() + "/test/" + "LessThanOrEqual"))
-                                .field("integer", 5)
-                                .build(),
-                            Document::builder()
-                                .name(&(database().get_path() + "/test/" + "GreaterThan"))
-                                .field("integer", 6)
-                                .build(),
-                           ];
-                match query.run() {
-                    Ok(results) => {
-                        assert_eq!(results, expected);
-                    },
-                    Err(e) => {
-                        panic!("Failed to run query: {}", e);
+                block_on(async {
+                    let query = database().query()
+                                  .collections(test_collection())
+                                  .filter("integer", FilterOp::GREATER_THAN_OR_EQUAL(5));
+                    let expected = vec![
+                                Document::builder()
+                                    .name(&(database().get_path() + "/test/" + "GreaterThanOrEqual"))
+                                    .field("integer", 5)
+                                    .build(),
+                                Document::builder()
+                                    .name(&(database().get_path() + "/test/" + "LessThanOrEqual"))
+                                    .field("integer", 5)
+                                    .build(),
+                                Document::builder()
+                                    .name(&(database().get_path() + "/test/" + "GreaterThan"))
+                                    .field("integer", 6)
+                                    .build(),
+                               ];
+                    match query.run().await {
+                        Ok(results) => {
+                            assert_eq!(results, expected);
+                        },
+                        Err(e) => {
+                            panic!("Failed to run query: {}", e);
+                        }
                     }
-                }
+                })
             }
             #[test]
             fn array_contains() {
-                let query = database().query()
-                              .collections(test_collection())
-                              .filter("numbers", FilterOp::ARRAY_CONTAINS(5));
-                let name = database().get_path() + "/test/" + "ArrayContains";
-                let expected = vec![
-                                Document::builder()
-                                    .name(&name)
-                                    .field("numbers",
-                                           vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9]
-                                            .iter()
-                                            .map(|&v|
-                                                 FieldValue::from(v))
-                                                    .collect::<Vec<FieldValue>>())
-                                    .build()
-                           ];
-                match query.run() {
-                    Ok(results) => {
-                        assert_eq!(results, expected);
-                    },
-                    Err(e) => {
-                        panic!("Failed to run query: {}", e);
+                block_on(async {
+                    let query = database().query()
+                                  .collections(test_collection())
+                                  .filter("numbers", FilterOp::ARRAY_CONTAINS(5));
+                    let name = database().get_path() + "/test/" + "ArrayContains";
+                    let expected = vec![
+                                    Document::builder()
+                                        .name(&name)
+                                        .field("numbers",
+                                               vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9]
+                                                .iter()
+                                                .map(|&v|
+                                                     FieldValue::from(v))
+                                                        .collect::<Vec<FieldValue>>())
+                                        .build()
+                               ];
+                    match query.run().await {
+                        Ok(results) => {
+                            assert_eq!(results, expected);
+                        },
+                        Err(e) => {
+                            panic!("Failed to run query: {}", e);
+                        }
                     }
-                }
+                })
             }
         }
     }
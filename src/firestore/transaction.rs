@@ -0,0 +1,66 @@
+use crate::{
+    error::DatabaseError,
+    database::Database,
+    document::{
+        Document,
+    },
+    firestore::{
+        Firestore,
+        query::FirestoreQuery,
+    },
+    transaction::{
+        Transaction,
+    },
+    write::{
+        Write,
+    },
+};
+
+pub struct FirestoreTransaction {
+    firestore: Firestore,
+    pub(crate) id: String,
+    pub(crate) writes: Vec<Write>,
+}
+
+impl FirestoreTransaction {
+    pub(crate) fn new(firestore: Firestore, id: String) -> Self {
+        Self {
+            firestore,
+            id,
+            writes: Vec::new(),
+        }
+    }
+}
+
+impl<'a> Transaction<'a> for FirestoreTransaction {
+    type Database = Firestore;
+
+    fn get_document<A: ToString, B: ToString>(
+        &mut self,
+        collection_id: A,
+        document_id: B,
+        ) -> Result<Document, DatabaseError> {
+        let path = self.firestore.document_path(&collection_id.to_string(),
+                                                &document_id.to_string());
+        self.firestore.db()
+            .projects()
+            .databases_documents_get(&path)
+            .transaction(&self.id)
+            .doit()
+            .map(|(_r, d)| Document::from(d))
+            .map_err(|e| DatabaseError::from(e))
+    }
+    fn run_query(
+        &mut self,
+        query: FirestoreQuery,
+        ) -> Result<Vec<Document>, DatabaseError> {
+        query.database(&self.firestore)
+             .run_in_transaction(&self.id)
+    }
+    fn write(&mut self, write: Write) {
+        self.writes.push(write);
+    }
+    fn generate_id(&self) -> String {
+        self.firestore.generate_id()
+    }
+}
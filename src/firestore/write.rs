@@ -0,0 +1,209 @@
+use crate::{
+    database::Database,
+    document::{
+        FieldValue,
+    },
+    error::{
+        DatabaseError,
+        DatabaseErrorKind,
+    },
+    firestore::{
+        Firestore,
+    },
+    write::{
+        Write,
+        FieldTransform,
+        Precondition,
+    },
+};
+
+fn array_value(values: Vec<FieldValue>) -> google_firestore::ArrayValue {
+    google_firestore::ArrayValue {
+        values: Some(values.into_iter()
+                           .map(|v| v.into())
+                           .collect()),
+    }
+}
+
+impl From<FieldTransform> for google_firestore::FieldTransform {
+    fn from(transform: FieldTransform) -> Self {
+        let field_path = Some(transform.field_path().to_string());
+        let transform = match transform {
+            FieldTransform::SetToServerValue(_, value) => google_firestore::FieldTransform {
+                set_to_server_value: Some(value.to_string()),
+                ..Default::default()
+            },
+            FieldTransform::Increment(_, value) => google_firestore::FieldTransform {
+                increment: Some(value.into()),
+                ..Default::default()
+            },
+            FieldTransform::Maximum(_, value) => google_firestore::FieldTransform {
+                maximum: Some(value.into()),
+                ..Default::default()
+            },
+            FieldTransform::Minimum(_, value) => google_firestore::FieldTransform {
+                minimum: Some(value.into()),
+                ..Default::default()
+            },
+            FieldTransform::AppendMissingElements(_, values) => google_firestore::FieldTransform {
+                append_missing_elements: Some(array_value(values)),
+                ..Default::default()
+            },
+            FieldTransform::RemoveAllFromArray(_, values) => google_firestore::FieldTransform {
+                remove_all_from_array: Some(array_value(values)),
+                ..Default::default()
+            },
+        };
+        google_firestore::FieldTransform {
+            field_path,
+            ..transform
+        }
+    }
+}
+
+impl From<Precondition> for google_firestore::Precondition {
+    fn from(precondition: Precondition) -> Self {
+        match precondition {
+            Precondition::Exists(exists) => google_firestore::Precondition {
+                exists: Some(exists),
+                ..Default::default()
+            },
+            Precondition::UpdateTime(time) => google_firestore::Precondition {
+                update_time: Some(time),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+// Firestore fails writes whose update time precondition does not
+// hold with FAILED_PRECONDITION, which it also returns for errors
+// unrelated to the written documents. The error of writes with
+// such a precondition is reported as PreconditionFailed
+pub(crate) fn precondition_error<'p, P>(error: DatabaseError, mut preconditions: P) -> DatabaseError
+    where P: Iterator<Item=&'p Precondition>
+{
+    let update_time = preconditions.any(|p| match p {
+        Precondition::UpdateTime(_) => true,
+        Precondition::Exists(_) => false,
+    });
+    if update_time && error.kind() == DatabaseErrorKind::FailedPrecondition {
+        DatabaseError::new(DatabaseErrorKind::PreconditionFailed, error.message())
+    } else {
+        error
+    }
+}
+
+impl Firestore {
+    // the database resource name, used for commits and transactions
+    pub(crate) fn database_name(&self) -> String {
+        format!("projects/{}/databases/(default)", self.project_id)
+    }
+    pub(crate) fn document_path(&self, collection_id: &str, document_id: &str) -> String {
+        format!("{}/{}", self.collection_path(collection_id), document_id)
+    }
+    // converts a Write to Firestore Writes with full document paths.
+    // The transforms of an update follow it as a separate write
+    pub(crate) fn firestore_writes(&self, write: Write) -> Vec<google_firestore::Write> {
+        match write {
+            Write::Update { collection_id, document_id, document, mask, transforms, precondition } => {
+                let mut writes = vec![self.firestore_write(Write::Update {
+                    collection_id: collection_id.clone(),
+                    document_id: document_id.clone(),
+                    document,
+                    mask,
+                    transforms: Vec::new(),
+                    precondition,
+                })];
+                if !transforms.is_empty() {
+                    writes.push(self.firestore_write(Write::Transform {
+                        collection_id,
+                        document_id,
+                        transforms,
+                    }));
+                }
+                writes
+            },
+            write => vec![self.firestore_write(write)],
+        }
+    }
+    fn firestore_write(&self, write: Write) -> google_firestore::Write {
+        let path = self.document_path(write.collection_id(), write.document_id());
+        match write {
+            Write::Create { document, .. } => google_firestore::Write {
+                update: Some(google_firestore::Document {
+                    name: Some(path),
+                    create_time: None,
+                    update_time: None,
+                    ..document.into()
+                }),
+                current_document: Some(google_firestore::Precondition {
+                    exists: Some(false),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            Write::Update { document, mask, precondition, .. } => google_firestore::Write {
+                update: Some(google_firestore::Document {
+                    name: Some(path),
+                    create_time: None,
+                    update_time: None,
+                    ..document.into()
+                }),
+                update_mask: mask.map(|mask| google_firestore::DocumentMask {
+                    field_paths: Some(mask.iter()
+                                          .map(|f| f.to_string())
+                                          .collect()),
+                }),
+                current_document: Some(precondition.into()),
+                ..Default::default()
+            },
+            // an update without precondition creates missing documents
+            Write::Set { document, options, .. } => google_firestore::Write {
+                update_mask: options.mask(&document).map(|mask| google_firestore::DocumentMask {
+                    field_paths: Some(mask.iter()
+                                          .map(|f| f.to_string())
+                                          .collect()),
+                }),
+                update: Some(google_firestore::Document {
+                    name: Some(path),
+                    create_time: None,
+                    update_time: None,
+                    ..document.into()
+                }),
+                ..Default::default()
+            },
+            Write::Delete { precondition, .. } => google_firestore::Write {
+                delete: Some(path),
+                current_document: precondition.map(|p| p.into()),
+                ..Default::default()
+            },
+            Write::Transform { transforms, .. } => google_firestore::Write {
+                transform: Some(google_firestore::DocumentTransform {
+                    document: Some(path),
+                    field_transforms: Some(transforms.into_iter()
+                                                     .map(|t| t.into())
+                                                     .collect()),
+                }),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn precondition_errors() {
+        let error = || DatabaseError::new(DatabaseErrorKind::FailedPrecondition, "failed");
+        let update_time = Precondition::UpdateTime("2024-01-01T00:00:00.000000Z".to_string());
+        assert_eq!(precondition_error(error(), Some(&update_time).into_iter()).kind(),
+                   DatabaseErrorKind::PreconditionFailed);
+        assert_eq!(precondition_error(error(), Some(&Precondition::Exists(true)).into_iter()).kind(),
+                   DatabaseErrorKind::FailedPrecondition);
+        let not_found = DatabaseError::new(DatabaseErrorKind::NotFound, "missing");
+        assert_eq!(precondition_error(not_found, Some(&update_time).into_iter()).kind(),
+                   DatabaseErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::document::Document;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+// the length of the ids the Firestore client libraries generate
+pub const ID_LENGTH: usize = 20;
+
+// IdGenerators name documents which are created without a name.
+// Closures returning ids can be used, e.g. for deterministic tests
+pub trait IdGenerator: Send + Sync {
+    fn generate(&self) -> String;
+}
+
+impl<F: Fn() -> String + Send + Sync> IdGenerator for F {
+    fn generate(&self) -> String {
+        self()
+    }
+}
+
+// random alphanumeric ids of ID_LENGTH characters,
+// so ids of concurrent clients do not collide
+pub struct RandomIds {
+    // randomly keyed when created
+    state: RandomState,
+    counter: AtomicU64,
+}
+
+impl Default for RandomIds {
+    fn default() -> Self {
+        RandomIds {
+            state: RandomState::new(),
+            counter: AtomicU64::new(0),
+        }
+    }
+}
+
+impl IdGenerator for RandomIds {
+    fn generate(&self) -> String {
+        let n = self.counter.fetch_add(1, Ordering::Relaxed);
+        (0..ID_LENGTH)
+            .map(|i| {
+                let mut hasher = self.state.build_hasher();
+                (n, i).hash(&mut hasher);
+                ALPHABET[(hasher.finish() % ALPHABET.len() as u64) as usize] as char
+            })
+            .collect()
+    }
+}
+
+// an IdGenerator shared by the clones of a database handle,
+// random ids by default
+#[derive(Clone)]
+pub(crate) struct SharedIds(Arc<dyn IdGenerator>);
+
+impl SharedIds {
+    pub(crate) fn new<G: IdGenerator + 'static>(ids: G) -> Self {
+        SharedIds(Arc::new(ids))
+    }
+}
+
+impl Default for SharedIds {
+    fn default() -> Self {
+        SharedIds::new(RandomIds::default())
+    }
+}
+
+impl IdGenerator for SharedIds {
+    fn generate(&self) -> String {
+        self.0.generate()
+    }
+}
+
+// the document, named with a generated id if it has no name
+pub fn named_or<F: FnOnce() -> String>(document: Document, generate: F) -> Document {
+    if document.name().is_empty() {
+        document.named(&generate())
+    } else {
+        document
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn random_ids() {
+        let ids = RandomIds::default();
+        let a = ids.generate();
+        let b = ids.generate();
+        assert_eq!(a.len(), ID_LENGTH);
+        assert!(a.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(a, b);
+    }
+    #[test]
+    fn named_or_generated() {
+        let named = named_or(Document::builder().name("A").build(), || "B".to_string());
+        assert_eq!(named.name(), "A");
+        let unnamed = named_or(Document::builder().build(), || "B".to_string());
+        assert_eq!(unnamed.name(), "B");
+    }
+}
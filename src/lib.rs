@@ -10,43 +10,65 @@ extern crate serde_json as json;
 extern crate logger;
 extern crate futures;
 extern crate lazy_static;
-extern crate actix_web;
 
 pub mod access;
 mod client;
+mod blocking;
+pub mod id;
 pub mod document;
 pub mod collection;
 pub mod error;
 pub mod query;
 pub mod firestore;
+pub mod memory;
 pub mod database;
+pub mod write;
+pub mod batch;
+pub mod list;
+pub mod transaction;
 
 use lazy_static::lazy_static;
-use firestore::access::get_service_account_key;
 use crate::firestore::Firestore;
 use crate::database::Database;
-use access::{Access};
+use crate::error::DatabaseError;
 
-// lazy_static makes it possible to run code to initialize statics
+// lazy_static makes it possible to run code to initialize statics.
+// DATABASE is a default handle for convenience, other
+// Firestore handles can be created and used alongside it
 lazy_static! {
-    static ref DATABASE: Firestore = {
-        let key = get_service_account_key();
-        let access = Access::from(key);
-        Firestore::new(access)
-    };
+    static ref DATABASE: Result<Firestore, DatabaseError> = Firestore::from_env();
 }
 
-pub fn init() {
+// initializes the database and returns an error
+// if no valid credentials could be found
+pub fn init() -> Result<(), DatabaseError> {
     // DATABASE is initialized here! (due to lazy_static)
-    database();
+    try_database().map(|_| ())
 }
 
+pub fn try_database() -> Result<&'static Firestore, DatabaseError> {
+    DATABASE.as_ref().map_err(Clone::clone)
+}
+
+pub fn try_collection<T: ToString>(id: T) -> Result<firestore::collection::FirestoreCollection<'static>, DatabaseError> {
+    try_database().map(|database| database.collection(id))
+}
+
+// panics if the database could not be initialized
+#[deprecated(note = "use try_database, which returns the error instead of panicking")]
 pub fn database() -> &'static Firestore {
-    &DATABASE
+    match try_database() {
+        Ok(database) => database,
+        Err(e) => panic!("Failed to initialize the database: {}", e),
+    }
 }
 
+#[deprecated(note = "use try_collection, which returns the error instead of panicking")]
 pub fn collection<T: ToString>(id: T) -> firestore::collection::FirestoreCollection<'static> {
-    database().collection(id)
+    match try_collection(id) {
+        Ok(collection) => collection,
+        Err(e) => panic!("Failed to initialize the database: {}", e),
+    }
 }
 
 #[cfg(test)]
@@ -54,8 +76,10 @@ mod tests {
     #![allow(unused)]
     use super::*;
 
+    // needs credentials, run with `cargo test -- --ignored`
     #[test]
+    #[ignore]
     fn authentication() {
-        init();
+        init().unwrap();
     }
 }
@@ -0,0 +1,120 @@
+use crate::{
+    document::{
+        Document,
+        FieldPath,
+    },
+    error::{
+        DatabaseError,
+        DatabaseErrorKind,
+    },
+    query::{
+        eval,
+        ordering::Ordering,
+    },
+};
+
+// ListOptions configure how the documents
+// of a collection are listed
+#[derive(Clone, Default)]
+pub struct ListOptions {
+    pub(crate) page_size: Option<u32>,
+    pub(crate) orders: Vec<google_firestore::Order>,
+    pub(crate) show_missing: bool,
+    pub(crate) mask: Option<Vec<FieldPath>>,
+}
+
+impl ListOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // max number of documents requested at once,
+    // all pages are still read
+    pub fn page_size(self, page_size: u32) -> Self {
+        Self {
+            page_size: Some(page_size),
+            ..self
+        }
+    }
+    // define result orders for field
+    pub fn order_by(self, field: &str, direction: Ordering) -> Self {
+        Self {
+            orders: {
+                let mut neworders = self.orders.clone();
+                neworders.push(google_firestore::Order {
+                    field: Some(google_firestore::FieldReference {
+                        field_path: Some(field.to_string()),
+                    }),
+                    direction: Some(direction.to_string()),
+                });
+                neworders
+            },
+            ..self
+        }
+    }
+    // also list documents which do not exist,
+    // but have documents in subcollections
+    pub fn show_missing(self, show_missing: bool) -> Self {
+        Self {
+            show_missing,
+            ..self
+        }
+    }
+    // only return these fields of the documents
+    pub fn mask(self, mask: Vec<FieldPath>) -> Self {
+        Self {
+            mask: Some(mask),
+            ..self
+        }
+    }
+    // the orders in the format of the orderBy
+    // parameter of the list method, e.g. "a desc, b"
+    pub(crate) fn order_by_param(&self) -> Option<String> {
+        if self.orders.is_empty() {
+            return None;
+        }
+        Some(self.orders.iter()
+                 .filter_map(|order| {
+                     let field = order.field.as_ref()?.field_path.clone()?;
+                     Some(match order.direction.as_ref().map(|d| d.as_str()) {
+                         Some("DESCENDING") => format!("{} desc", field),
+                         _ => field,
+                     })
+                 })
+                 .collect::<Vec<_>>()
+                 .join(", "))
+    }
+    // Firestore can only list missing documents in the order of their names
+    pub(crate) fn check(&self) -> Result<(), DatabaseError> {
+        if self.show_missing && !self.orders.is_empty() {
+            Err(DatabaseError::new(
+                    DatabaseErrorKind::InvalidArgument,
+                    "show_missing can not be used with order_by"))
+        } else {
+            Ok(())
+        }
+    }
+    // sorts and masks documents like Firestore would list them
+    pub(crate) fn apply(&self, mut documents: Vec<Document>) -> Vec<Document> {
+        let orders = eval::effective_orders(&google_firestore::Filter::default(), &self.orders);
+        documents.sort_by(|a, b| eval::compare_documents(&orders, a, b));
+        match &self.mask {
+            Some(mask) => documents.iter()
+                                   .map(|d| d.masked(mask))
+                                   .collect(),
+            None => documents,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn order_by_param() {
+        assert_eq!(ListOptions::new().order_by_param(), None);
+        let options = ListOptions::new()
+            .order_by("count", Ordering::DESCENDING)
+            .order_by("name", Ordering::ASCENDING);
+        assert_eq!(options.order_by_param(), Some("count desc, name".to_string()));
+    }
+}
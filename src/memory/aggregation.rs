@@ -0,0 +1,37 @@
+use crate::{
+    database::DatabaseFuture,
+    document::Document,
+    firestore::query::FirestoreQuery,
+    memory::query::MemoryQuery,
+    query::aggregation::{
+        self,
+        Aggregate,
+        Sum,
+    },
+};
+use futures::future;
+
+impl<'a> MemoryQuery<'a> {
+    // the documents to aggregate, with all their fields
+    fn aggregated(self) -> Vec<Document> {
+        MemoryQuery {
+            query: FirestoreQuery {
+                select: None,
+                ..self.query
+            },
+            ..self
+        }.results()
+    }
+}
+
+impl<'a> Aggregate for MemoryQuery<'a> {
+    fn count(self) -> DatabaseFuture<u64> {
+        Box::pin(future::ok(self.aggregated().len() as u64))
+    }
+    fn sum(self, field: &str) -> DatabaseFuture<Sum> {
+        Box::pin(future::ok(aggregation::sum(&self.aggregated(), field)))
+    }
+    fn avg(self, field: &str) -> DatabaseFuture<Option<f64>> {
+        Box::pin(future::ok(aggregation::avg(&self.aggregated(), field)))
+    }
+}
@@ -0,0 +1,334 @@
+use crate::{
+    database::{Database, DatabaseFuture, DocumentStream, Lookup},
+    list::{ListOptions},
+    document::{Document, FieldPath},
+    write::{FieldTransform, Precondition, SetOptions},
+    memory::{MemoryDatabase},
+    collection::{Collection},
+};
+use std::vec::Vec;
+
+pub struct MemoryCollection<'a> {
+    database: &'a MemoryDatabase,
+    collection_id: String,
+}
+
+impl<'a> Collection<'a> for MemoryCollection<'a> {
+    type Database = MemoryDatabase;
+    fn new<T: ToString>(database: &'a MemoryDatabase, id: T) -> Self {
+        Self {
+            database,
+            collection_id: id.to_string(),
+        }
+    }
+
+    fn get_path(&self) -> String {
+        self.database.collection_path(self.collection_id.clone())
+    }
+
+    fn get_document<T: ToString>(
+        &self,
+        document_id: T,
+        ) -> DatabaseFuture<Document> {
+        self.database.get_document(self.collection_id.clone(), document_id)
+    }
+    fn get_document_masked<T: ToString>(
+        &self,
+        document_id: T,
+        mask: Option<Vec<FieldPath>>,
+        ) -> DatabaseFuture<Document> {
+        self.database.get_document_masked(self.collection_id.clone(), document_id, mask)
+    }
+
+    fn get_documents_by_id<T: ToString>(
+        &self,
+        document_ids: &[T],
+        ) -> DatabaseFuture<Vec<Lookup>> {
+        let paths: Vec<String> = document_ids.iter()
+            .map(|id| format!("{}/{}", self.collection_id, id.to_string()))
+            .collect();
+        self.database.get_documents_by_id(&paths)
+    }
+
+    fn create_document(
+        &self,
+        document: Document,
+        ) -> DatabaseFuture<String> {
+        self.database.create_document(self.collection_id.clone(), document)
+    }
+    fn set_document(
+        &self,
+        document: Document,
+        options: SetOptions,
+        ) -> DatabaseFuture<()> {
+        self.database.set_document(self.collection_id.clone(), document, options)
+    }
+    fn update_document<T: ToString>(
+        &self,
+        document_id: T,
+        document: Document,
+        mask: Option<Vec<FieldPath>>,
+        ) -> DatabaseFuture<Document> {
+        self.database.update_document(self.collection_id.clone(), document_id, document, mask)
+    }
+    fn update_document_if<T: ToString>(
+        &self,
+        document_id: T,
+        document: Document,
+        mask: Option<Vec<FieldPath>>,
+        precondition: Precondition,
+        ) -> DatabaseFuture<Document> {
+        self.database.update_document_if(self.collection_id.clone(), document_id, document, mask, precondition)
+    }
+    fn delete_document<T: ToString>(
+        &self,
+        document_id: T,
+        ) -> DatabaseFuture<()> {
+        self.database.delete_document(self.collection_id.clone(), document_id)
+    }
+    fn delete_document_if<T: ToString>(
+        &self,
+        document_id: T,
+        precondition: Option<Precondition>,
+        ) -> DatabaseFuture<()> {
+        self.database.delete_document_if(self.collection_id.clone(), document_id, precondition)
+    }
+    fn transform_document<T: ToString>(
+        &self,
+        document_id: T,
+        transforms: Vec<FieldTransform>,
+        ) -> DatabaseFuture<()> {
+        self.database.transform_document(self.collection_id.clone(), document_id, transforms)
+    }
+
+    fn list_documents(
+        &self,
+        options: ListOptions,
+        ) -> DocumentStream {
+        self.database.list_documents(self.collection_id.clone(), options)
+    }
+    fn get_documents(
+        &self
+        ) -> DatabaseFuture<Vec<Document>> {
+        self.database.get_documents(self.collection_id.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(unused)]
+    use crate::{
+        error::DatabaseErrorKind,
+        document::{
+            Document,
+            FieldValue,
+            tests::{
+                test_document,
+            },
+        },
+    };
+    use super::*;
+    use crate::query::ordering::Ordering;
+    use futures::{
+        executor::block_on,
+        stream::TryStreamExt,
+    };
+    #[test]
+    fn document_test() {
+        block_on(async {
+            let database = MemoryDatabase::default();
+            let collection = database.collection("test");
+            let id = "TestDocument";
+            let doc = test_document(format!("{}/{}", collection.get_path(), id));
+            collection.create_document(doc.clone()).await.unwrap();
+            let created = collection.get_document(id).await.unwrap();
+            assert_eq!(created, doc);
+            assert!(created.create_time().is_some());
+            let error = collection.create_document(doc.clone())
+                .await.expect_err("Created the same document twice!");
+            assert_eq!(error.kind(), DatabaseErrorKind::AlreadyExists);
+            collection.delete_document(id).await.unwrap();
+            let error = collection.get_document(id)
+                .await.expect_err("Got document after calling DELETE!");
+            assert_eq!(error.kind(), DatabaseErrorKind::NotFound);
+        })
+    }
+    #[test]
+    fn update_document() {
+        block_on(async {
+            let database = MemoryDatabase::default();
+            let collection = database.collection("test");
+            let id = "TestDocument";
+            let doc = test_document(format!("{}/{}", collection.get_path(), id));
+            collection.update_document(id, doc.clone(), None)
+                .await.expect_err("Updated a missing document!");
+            collection.create_document(doc.clone()).await.unwrap();
+            let update = Document::builder()
+                .field("test_number", 43)
+                .field("test_bool", true)
+                .build();
+            let updated = collection.update_document(
+                    id,
+                    update,
+                    Some(vec![FieldPath::from("test_number"), FieldPath::from("test_string")]))
+                .await
+                .unwrap();
+            let expected = Document::builder()
+                .name(doc.id())
+                .field("test_number", 43)
+                .build();
+            assert_eq!(updated, expected);
+            assert_eq!(collection.get_document(id).await.unwrap(), expected);
+            let replaced = collection.update_document(id, doc.clone(), None).await.unwrap();
+            assert_eq!(replaced, doc);
+        })
+    }
+    #[test]
+    fn get_documents() {
+        block_on(async {
+            let database = MemoryDatabase::default();
+            let collection = database.collection("test");
+            for id in &["B", "A", "C"] {
+                collection.create_document(test_document(id)).await.unwrap();
+            }
+            database.collection("other")
+                .create_document(test_document("D")).await.unwrap();
+            let names: Vec<String> = collection.get_documents()
+                .await
+                .unwrap()
+                .iter()
+                .map(|d| d.name().to_string())
+                .collect();
+            assert_eq!(names, vec!["A", "B", "C"]);
+        })
+    }
+    #[test]
+    fn list_documents() {
+        block_on(async {
+            let database = MemoryDatabase::default();
+            let collection = database.collection("test");
+            for (id, count) in &[("A", 2), ("B", 3), ("C", 1)] {
+                collection.create_document(Document::builder()
+                                               .name(id)
+                                               .field("count", *count)
+                                               .field("other", true)
+                                               .build())
+                    .await
+                    .unwrap();
+            }
+            database.collection("test/D/sub")
+                .create_document(test_document("E")).await.unwrap();
+            let options = ListOptions::new()
+                .order_by("count", Ordering::DESCENDING)
+                .mask(vec![FieldPath::from("count")]);
+            let documents: Vec<Document> = collection.list_documents(options.clone())
+                .try_collect()
+                .await
+                .unwrap();
+            let names: Vec<&str> = documents.iter().map(|d| d.name()).collect();
+            assert_eq!(names, vec!["B", "A", "C"]);
+            assert_eq!(documents[0].fields().len(), 1);
+            // missing documents are listed in the order of their names
+            let documents: Vec<Document> = collection.list_documents(ListOptions::new().show_missing(true))
+                .try_collect()
+                .await
+                .unwrap();
+            let names: Vec<&str> = documents.iter().map(|d| d.name()).collect();
+            assert_eq!(names, vec!["A", "B", "C", "D"]);
+            let error = collection.list_documents(options.show_missing(true))
+                .try_collect::<Vec<Document>>()
+                .await
+                .expect_err("Listed missing documents in a custom order!");
+            assert_eq!(error.kind(), DatabaseErrorKind::InvalidArgument);
+            assert_eq!(collection.get_documents().await.unwrap().len(), 3);
+        })
+    }
+    #[test]
+    fn preconditions() {
+        block_on(async {
+            let database = MemoryDatabase::default();
+            let collection = database.collection("test");
+            let id = "TestDocument";
+            collection.create_document(test_document(id)).await.unwrap();
+            let read = collection.get_document(id).await.unwrap();
+            let update = Document::builder().field("test_number", 43).build();
+            let mask = Some(vec![FieldPath::from("test_number")]);
+            collection.update_document_if(id, update.clone(), mask.clone(), Precondition::unchanged(&read))
+                .await
+                .unwrap();
+            // the document was changed since it was read
+            let error = collection.update_document_if(id, update.clone(), mask.clone(), Precondition::unchanged(&read))
+                .await
+                .unwrap_err();
+            assert_eq!(error.kind(), DatabaseErrorKind::PreconditionFailed);
+            let error = collection.delete_document_if(id, Some(Precondition::unchanged(&read)))
+                .await
+                .unwrap_err();
+            assert_eq!(error.kind(), DatabaseErrorKind::PreconditionFailed);
+            let error = collection.delete_document_if("Missing", Some(Precondition::Exists(true)))
+                .await
+                .unwrap_err();
+            assert_eq!(error.kind(), DatabaseErrorKind::NotFound);
+            // updates with Exists(false) create documents
+            collection.update_document_if("New", update, mask, Precondition::Exists(false))
+                .await
+                .unwrap();
+            assert!(collection.get_document("New").await.unwrap().create_time().is_some());
+            let read = collection.get_document(id).await.unwrap();
+            collection.delete_document_if(id, Some(Precondition::unchanged(&read)))
+                .await
+                .unwrap();
+        })
+    }
+    #[test]
+    fn generated_ids() {
+        block_on(async {
+            let next = std::sync::atomic::AtomicUsize::new(0);
+            let database = MemoryDatabase::default()
+                .with_id_generator(move || format!("id{}", next.fetch_add(1, std::sync::atomic::Ordering::SeqCst)));
+            let collection = database.collection("test");
+            let path = collection.create_document(Document::builder().field("a", 1).build())
+                .await
+                .unwrap();
+            assert_eq!(path, format!("{}/id0", collection.get_path()));
+            assert_eq!(collection.get_document("id0").await.unwrap().get("a"),
+                       Ok(&FieldValue::from(1)));
+            database.batch()
+                .create("test", Document::default())
+                .create("test", Document::builder().name("named").build())
+                .commit()
+                .await
+                .unwrap();
+            let names: Vec<String> = collection.get_documents()
+                .await
+                .unwrap()
+                .iter()
+                .map(|d| d.name().to_string())
+                .collect();
+            assert_eq!(names, vec!["id0", "id1", "named"]);
+        })
+    }
+    #[test]
+    fn get_documents_by_id() {
+        block_on(async {
+            let database = MemoryDatabase::default();
+            let collection = database.collection("test");
+            for id in &["A", "B"] {
+                collection.create_document(test_document(id)).await.unwrap();
+            }
+            database.collection("test/A/sub")
+                .create_document(test_document("C")).await.unwrap();
+            let lookups = database.get_documents_by_id(&["test/B", "test/Missing", "test/A/sub/C", "test/A"])
+                .await
+                .unwrap();
+            let names: Vec<Option<String>> = lookups.iter()
+                .map(|l| l.clone().found().map(|d| d.name().to_string()))
+                .collect();
+            assert_eq!(names, vec![Some("B".to_string()), None, Some("C".to_string()), Some("A".to_string())]);
+            assert_eq!(lookups[1], Lookup::Missing(format!("{}/Missing", collection.get_path())));
+            let lookups = collection.get_documents_by_id(&["A", "Missing"]).await.unwrap();
+            assert!(lookups[0].clone().found().is_some());
+            assert!(lookups[1].clone().found().is_none());
+        })
+    }
+}
@@ -0,0 +1,30 @@
+use crate::{
+    query::filter::{
+        Filter,
+        FilterExpr,
+    },
+};
+use crate::memory::query::{
+    MemoryQuery,
+};
+
+impl<'a> Filter for MemoryQuery<'a> {
+    fn filter_expr(self, expr: FilterExpr) -> Self {
+        Self {
+            query: self.query.filter_expr(expr),
+            ..self
+        }
+    }
+    fn and(self, other: Self) -> Self {
+        Self {
+            query: Filter::and(self.query, other.query),
+            ..self
+        }
+    }
+    fn or(self, other: Self) -> Self {
+        Self {
+            query: Filter::or(self.query, other.query),
+            ..self
+        }
+    }
+}
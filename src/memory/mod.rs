@@ -0,0 +1,320 @@
+use crate::access::{Access};
+use crate::query::{Query};
+use crate::document::{Document, FieldPath};
+use crate::write::{Write, Precondition};
+use crate::list::{ListOptions};
+use crate::error::*;
+use crate::id::{IdGenerator, SharedIds};
+
+pub mod collection;
+pub mod query;
+pub mod filter;
+pub mod write;
+pub mod transaction;
+pub mod aggregation;
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicI64, Ordering as AtomicOrdering};
+
+use futures::{
+    future,
+    stream,
+};
+use crate::blocking::blocking;
+
+pub type MemoryAccess = Access<()>;
+
+// documents of a single collection, ordered by their names
+pub(crate) type DocumentMap = BTreeMap<String, Document>;
+// all collections by their paths relative to the database root
+pub(crate) type Collections = BTreeMap<String, DocumentMap>;
+
+// MemoryDatabase keeps all documents in process, so code written
+// against the Database traits can be tested without a server.
+// Clones share the same documents.
+#[derive(Clone)]
+pub struct MemoryDatabase
+{
+    pub(crate) collections: Arc<RwLock<Collections>>,
+    project_id: String,
+    ids: SharedIds,
+}
+
+impl Default for MemoryDatabase {
+    fn default() -> Self {
+        MemoryDatabase::new(Access {
+            project_id: "memory".to_string(),
+            access: (),
+        })
+    }
+}
+
+// current time in the RFC 3339 format Firestore uses for timestamps.
+// Timestamps are strictly increasing, so every write gets a
+// distinct update time which transactions can compare
+pub(crate) fn timestamp() -> String {
+    static LAST: AtomicI64 = AtomicI64::new(0);
+    let time = chrono::offset::Utc::now();
+    let now = time.timestamp() * 1_000_000 + time.timestamp_subsec_micros() as i64;
+    let micros = LAST.fetch_update(AtomicOrdering::SeqCst,
+                                   AtomicOrdering::SeqCst,
+                                   |last| Some(std::cmp::max(now, last + 1)))
+                     .map(|last| std::cmp::max(now, last + 1))
+                     .unwrap_or(now);
+    (time + chrono::Duration::microseconds(micros - now))
+        .format("%Y-%m-%dT%H:%M:%S%.6fZ")
+        .to_string()
+}
+
+impl MemoryDatabase {
+    // name documents created without a name with ids of the generator
+    pub fn with_id_generator<G: IdGenerator + 'static>(self, ids: G) -> Self {
+        Self {
+            ids: SharedIds::new(ids),
+            ..self
+        }
+    }
+    // reads a document without waiting, for get_document and transactions
+    pub(crate) fn read_document(
+        &self,
+        collection_id: &str,
+        document_id: &str,
+        mask: Option<&[FieldPath]>,
+        ) -> Result<Document, DatabaseError> {
+        let collections = self.collections.read().unwrap();
+        collections.get(collection_id)
+            .and_then(|documents| documents.get(document_id))
+            .map(|document| match mask {
+                Some(mask) => document.masked(mask),
+                None => document.clone(),
+            })
+            .ok_or_else(|| DatabaseError::new(
+                    DatabaseErrorKind::NotFound,
+                    format!("Document not found: {}/{}",
+                            self.collection_path(collection_id),
+                            document_id)))
+    }
+    // documents of a collection which do not exist
+    // themselves, but have subcollections with documents
+    fn missing_documents(
+        &self,
+        collections: &Collections,
+        collection_id: &str,
+        ) -> Vec<Document> {
+        let prefix = format!("{}/", collection_id);
+        let existing = collections.get(collection_id);
+        let mut names: Vec<&str> = collections.iter()
+            .filter(|(_, documents)| !documents.is_empty())
+            .filter_map(|(path, _)| path.strip_prefix(&prefix))
+            .filter_map(|rest| rest.split("/").next())
+            .filter(|name| existing.map(|d| !d.contains_key(*name)).unwrap_or(true))
+            .collect();
+        names.dedup();
+        names.into_iter()
+            .map(|name| Document::builder()
+                            .name(&format!("{}/{}",
+                                           self.collection_path(collection_id),
+                                           name))
+                            .build())
+            .collect()
+    }
+}
+
+use super::{
+    collection::Collection,
+    database::{Database, DatabaseFuture, DocumentStream, Lookup},
+};
+impl<'a> Database<'a> for MemoryDatabase
+{
+    type Access = MemoryAccess;
+    type Collection = collection::MemoryCollection<'a>;
+    type Query = query::MemoryQuery<'a>;
+    type Transaction = transaction::MemoryTransaction;
+
+    fn new(access: Self::Access) -> Self {
+        MemoryDatabase {
+            project_id: access.project_id,
+            collections: Arc::new(RwLock::new(BTreeMap::new())),
+            ids: SharedIds::default(),
+        }
+    }
+
+    fn get_path(&'a self) -> String {
+        format!(
+            "projects/{}/databases/(default)/documents",
+            self.project_id
+            )
+    }
+
+    fn collection<T: ToString>(
+        &'a self,
+        collection_id: T,
+        ) -> Self::Collection {
+        Self::Collection::new(self, collection_id)
+    }
+
+    fn query(&'a self) -> Self::Query
+    {
+        Self::Query::new().database(self)
+    }
+    fn create_document<T: ToString>(
+        &'a self,
+        collection_id: T,
+        document: Document
+        ) -> DatabaseFuture<String> {
+        let document = self.with_id(document);
+        let path = format!("{}/{}", self.collection_path(collection_id.to_string()), document.name());
+        let write = Write::Create {
+            collection_id: collection_id.to_string(),
+            document,
+        };
+        let mut collections = self.collections.write().unwrap();
+        Box::pin(future::ready(
+                self.apply(&mut collections, write)
+                    .map(|_| path)
+                ))
+    }
+    fn generate_id(&'a self) -> String {
+        self.ids.generate()
+    }
+    fn get_document_masked<A: ToString, B: ToString>(
+        &'a self,
+        collection_id: A,
+        document_id: B,
+        mask: Option<Vec<FieldPath>>,
+        ) -> DatabaseFuture<Document> {
+        Box::pin(future::ready(self.read_document(&collection_id.to_string(),
+                                                  &document_id.to_string(),
+                                                  mask.as_deref())))
+    }
+    fn get_documents_by_id<T: ToString>(
+        &'a self,
+        paths: &[T],
+        ) -> DatabaseFuture<Vec<Lookup>> {
+        let collections = self.collections.read().unwrap();
+        Box::pin(future::ok(paths.iter()
+            .map(|path| format!("{}/{}", self.get_path(), path.to_string()))
+            .map(|path| self.locate(&path)
+                 .and_then(|(collection_id, document_id)|
+                           collections.get(collection_id)
+                               .and_then(|documents| documents.get(document_id)))
+                 .cloned()
+                 .map(Lookup::Found)
+                 .unwrap_or(Lookup::Missing(path.clone())))
+            .collect())))
+    }
+    fn update_document_if<A: ToString, B: ToString>(
+        &'a self,
+        collection_id: A,
+        document_id: B,
+        document: Document,
+        mask: Option<Vec<FieldPath>>,
+        precondition: Precondition,
+        ) -> DatabaseFuture<Document> {
+        let collection_id = collection_id.to_string();
+        let document_id = document_id.to_string();
+        let write = Write::Update {
+            collection_id: collection_id.clone(),
+            document_id: document_id.clone(),
+            document,
+            mask,
+            transforms: Vec::new(),
+            precondition,
+        };
+        let mut collections = self.collections.write().unwrap();
+        Box::pin(future::ready(
+                self.apply(&mut collections, write)
+                    .map(|_| collections[&collection_id][&document_id].clone())
+                ))
+    }
+    fn delete_document_if<A: ToString, B: ToString>(
+        &'a self,
+        collection_id: A,
+        document_id: B,
+        precondition: Option<Precondition>,
+        ) -> DatabaseFuture<()> {
+        let write = Write::Delete {
+            collection_id: collection_id.to_string(),
+            document_id: document_id.to_string(),
+            precondition,
+        };
+        let mut collections = self.collections.write().unwrap();
+        Box::pin(future::ready(self.apply(&mut collections, write)))
+    }
+    fn list_documents<A: ToString>(
+        &'a self,
+        collection_id: A,
+        options: ListOptions,
+        ) -> DocumentStream {
+        if let Err(e) = options.check() {
+            return Box::pin(stream::iter(vec![Err(e)]));
+        }
+        let collection_id = collection_id.to_string();
+        let collections = self.collections.read().unwrap();
+        let mut documents: Vec<Document> = collections.get(&collection_id)
+            .map(|documents| documents.values().cloned().collect())
+            .unwrap_or(Vec::new());
+        if options.show_missing {
+            documents.extend(self.missing_documents(&collections, &collection_id));
+        }
+        Box::pin(stream::iter(options.apply(documents)
+                                     .into_iter()
+                                     .map(Ok)))
+    }
+    fn commit(
+        &'a self,
+        writes: Vec<Write>,
+        ) -> DatabaseFuture<()> {
+        let mut collections = self.collections.write().unwrap();
+        Box::pin(future::ready(self.apply_all(&mut collections, writes)))
+    }
+    fn begin_transaction(&'a self) -> Result<Self::Transaction, DatabaseError> {
+        Ok(transaction::MemoryTransaction::new(self.clone()))
+    }
+    fn commit_transaction(
+        &'a self,
+        transaction: Self::Transaction,
+        ) -> Result<(), DatabaseError> {
+        let mut collections = self.collections.write().unwrap();
+        for (path, update_time) in &transaction.reads {
+            let current = self.locate(path)
+                .and_then(|(collection_id, document_id)|
+                          collections.get(collection_id)
+                              .and_then(|documents| documents.get(document_id)))
+                .and_then(|document| document.update_time());
+            if current != *update_time {
+                return Err(DatabaseError::new(
+                        DatabaseErrorKind::Aborted,
+                        format!("Document {} was changed during the transaction", path)));
+            }
+        }
+        // documents may also have entered or left the results of a query
+        for (query, results) in &transaction.queries {
+            if transaction::versions(&query.results_in(&collections)) != *results {
+                return Err(DatabaseError::new(
+                        DatabaseErrorKind::Aborted,
+                        "The results of a query changed during the transaction"));
+            }
+        }
+        self.apply_all(&mut collections, transaction.writes)
+    }
+    fn rollback_transaction(
+        &'a self,
+        _transaction: Self::Transaction,
+        ) -> Result<(), DatabaseError> {
+        Ok(())
+    }
+    fn run_transaction<T, F>(
+        &'a self,
+        f: F,
+        ) -> DatabaseFuture<T>
+        where T: Send + 'static,
+              F: FnMut(&mut Self::Transaction) -> Result<T, DatabaseError> + Send + 'static
+    {
+        // f and the backoff between retries block, so they
+        // run on a blocking thread instead of the caller's
+        let database = self.clone();
+        blocking(move || crate::transaction::run(&database, f))
+    }
+}
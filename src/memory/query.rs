@@ -0,0 +1,437 @@
+use crate::{
+    database::DatabaseFuture,
+    document::{
+        Document,
+        FieldValue,
+    },
+    firestore::query::{
+        CollectionSelector,
+        FirestoreQuery,
+    },
+    memory::{
+        Collections,
+        MemoryDatabase,
+    },
+    query::{
+        cursor::Cursor,
+        ordering::Ordering,
+    },
+};
+use futures::future;
+
+// MemoryQuery builds the same structured query as FirestoreQuery
+// and evaluates it locally on the documents of a MemoryDatabase
+#[derive(Clone)]
+pub struct MemoryQuery<'a> {
+    pub(crate) database: Option<&'a MemoryDatabase>,
+    pub(crate) query: FirestoreQuery,
+}
+
+impl<'a> MemoryQuery<'a> {
+    // set the database to run the query on.
+    // A query without a database always returns no documents
+    pub fn database(self, database: &'a MemoryDatabase) -> Self {
+        Self {
+            database: Some(database),
+            ..self
+        }
+    }
+    // the results of the query in the current documents
+    pub(crate) fn results(&self) -> Vec<Document> {
+        match self.database {
+            Some(database) => self.results_in(&database.collections.read().unwrap()),
+            None => Vec::new(),
+        }
+    }
+    // the results of the query in the given collections
+    pub(crate) fn results_in(&self, collections: &Collections) -> Vec<Document> {
+        let selectors = &self.query.collections;
+        let prefix = self.query.parent
+            .as_ref()
+            .map(|parent| format!("{}/", parent))
+            .unwrap_or_default();
+        self.query.evaluate(
+            collections.iter()
+                .filter_map(|(path, documents)| Some((path.strip_prefix(&prefix)?, documents)))
+                .filter(|(path, _)| selectors
+                                        .iter()
+                                        .any(|c| selects(c, path)))
+                .flat_map(|(_, documents)| documents.values().cloned())
+            )
+    }
+}
+
+// true if the selector selects the collection at the
+// given path (relative to the parent of the query)
+fn selects(selector: &CollectionSelector, collection_path: &str) -> bool {
+    match selector.collection_id() {
+        Some(id) => if selector.all_descendants() {
+                        collection_path.rsplit("/").next() == Some(id)
+                    } else {
+                        collection_path == id
+                    },
+        None => false,
+    }
+}
+
+use crate::query::{Query};
+impl<'a> Query<'a, MemoryDatabase> for MemoryQuery<'a> {
+    fn new() -> Self {
+        MemoryQuery {
+            database: None,
+            query: FirestoreQuery::new(),
+        }
+    }
+    fn collections(self, collections: Vec<CollectionSelector>) -> Self
+    {
+        Self {
+            query: self.query.collections(collections),
+            ..self
+        }
+    }
+    fn order_by(self, field: &str, direction: Ordering) -> Self {
+        Self {
+            query: self.query.order_by(field, direction),
+            ..self
+        }
+    }
+    fn limit(self, limit: u32) -> Self {
+        Self {
+            query: self.query.limit(limit),
+            ..self
+        }
+    }
+    fn skip(self, skip: u32) -> Self {
+        Self {
+            query: self.query.skip(skip),
+            ..self
+        }
+    }
+    fn under(self, parent_document_path: &str) -> Self {
+        Self {
+            query: self.query.under(parent_document_path),
+            ..self
+        }
+    }
+    fn select(self, fields: &[&str]) -> Self {
+        Self {
+            query: self.query.select(fields),
+            ..self
+        }
+    }
+    fn start_at<C: Into<Cursor>>(self, cursor: C) -> Self {
+        Self {
+            query: self.query.start_at(cursor),
+            ..self
+        }
+    }
+    fn start_after<C: Into<Cursor>>(self, cursor: C) -> Self {
+        Self {
+            query: self.query.start_after(cursor),
+            ..self
+        }
+    }
+    fn end_at<C: Into<Cursor>>(self, cursor: C) -> Self {
+        Self {
+            query: self.query.end_at(cursor),
+            ..self
+        }
+    }
+    fn end_before<C: Into<Cursor>>(self, cursor: C) -> Self {
+        Self {
+            query: self.query.end_before(cursor),
+            ..self
+        }
+    }
+    fn cursor_values(&self, document: &Document) -> Vec<FieldValue> {
+        self.query.cursor_values(document)
+    }
+
+    fn fingerprint(&self) -> u64 {
+        self.query.fingerprint()
+    }
+
+    fn run(self) -> DatabaseFuture<Vec<Document>> {
+        Box::pin(future::ok(self.results()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(unused)]
+    use super::*;
+    use crate::{
+        database::Database,
+        document::FieldPath,
+        collection::Collection,
+        query::paginator::Paginator,
+        query::aggregation::{Aggregate, Sum},
+        query::filter::{
+            Filter,
+            FilterOp,
+            FilterExpr,
+        },
+    };
+    use futures::executor::block_on;
+
+    async fn test_database() -> MemoryDatabase {
+        let database = MemoryDatabase::default();
+        for (name, integer) in &[("A", 3), ("B", 6), ("C", 5), ("D", 5)] {
+            database.collection("test")
+                .create_document(Document::builder()
+                                     .name(name)
+                                     .field("integer", *integer)
+                                     .build())
+                .await
+                .unwrap();
+        }
+        database.collection("test")
+            .create_document(Document::builder()
+                                 .name("E")
+                                 .field("string", "TestString")
+                                 .build())
+            .await
+            .unwrap();
+        database
+    }
+    fn names(documents: Vec<Document>) -> Vec<String> {
+        documents.iter()
+            .map(|d| d.name().to_string())
+            .collect()
+    }
+    #[test]
+    fn all_documents() {
+        block_on(async {
+            let database = test_database().await;
+            let results = database.query()
+                .collections(vec![CollectionSelector::from("test")])
+                .run().await
+                .unwrap();
+            assert_eq!(names(results), vec!["A", "B", "C", "D", "E"]);
+        })
+    }
+    #[test]
+    fn order_limit_skip() {
+        block_on(async {
+            let database = test_database().await;
+            let results = database.query()
+                .collections(vec![CollectionSelector::from("test")])
+                .order_by("integer", Ordering::DESCENDING)
+                .skip(1)
+                .limit(2)
+                .run().await
+                .unwrap();
+            assert_eq!(names(results), vec!["D", "C"]);
+        })
+    }
+    #[test]
+    fn filter() {
+        block_on(async {
+            let database = test_database().await;
+            let results = database.query()
+                .collections(vec![CollectionSelector::from("test")])
+                .filter("integer", FilterOp::GREATER_THAN_OR_EQUAL(5))
+                .run().await
+                .unwrap();
+            assert_eq!(names(results), vec!["C", "D", "B"]);
+        })
+    }
+    #[test]
+    fn list_filter() {
+        block_on(async {
+            let database = test_database().await;
+            let results = database.query()
+                .collections(vec![CollectionSelector::from("test")])
+                .filter("integer", FilterOp::IN(vec![3, 6]))
+                .run().await
+                .unwrap();
+            assert_eq!(names(results), vec!["A", "B"]);
+            let results = database.query()
+                .collections(vec![CollectionSelector::from("test")])
+                .filter("integer", FilterOp::NOT_IN(vec![3, 6]))
+                .run().await
+                .unwrap();
+            assert_eq!(names(results), vec!["C", "D"]);
+        })
+    }
+    #[test]
+    fn combined_filters() {
+        block_on(async {
+            let database = test_database().await;
+            let query = || database.query()
+                .collections(vec![CollectionSelector::from("test")]);
+            // repeated filters are combined with AND
+            let results = query()
+                .filter("integer", FilterOp::GREATER_THAN_OR_EQUAL(5))
+                .filter("integer", FilterOp::NOT_EQUAL(6))
+                .run().await
+                .unwrap();
+            assert_eq!(names(results), vec!["C", "D"]);
+            let results = query()
+                .filter("integer", FilterOp::EQUAL(3))
+                .or(query().filter("string", FilterOp::EQUAL("TestString")))
+                .run().await
+                .unwrap();
+            assert_eq!(names(results), vec!["A", "E"]);
+            let results = query()
+                .filter_expr(FilterExpr::field("integer", FilterOp::IN(vec![3, 6])).negate_operators().unwrap())
+                .run().await
+                .unwrap();
+            assert_eq!(names(results), vec!["C", "D"]);
+        })
+    }
+    #[test]
+    fn select() {
+        block_on(async {
+            let database = test_database().await;
+            database.collection("test")
+                .create_document(Document::builder()
+                                     .name("F")
+                                     .field("integer", 7)
+                                     .field("string", "Wide")
+                                     .field("flag", true)
+                                     .build())
+                .await
+                .unwrap();
+            let results = database.query()
+                .collections(vec![CollectionSelector::from("test")])
+                .filter("integer", FilterOp::GREATER_THAN(5))
+                .select(&["string"])
+                .run().await
+                .unwrap();
+            assert_eq!(names(results.clone()), vec!["B", "F"]);
+            // the query is ordered by integer, so it is returned as well
+            assert_eq!(results[1].fields().len(), 2);
+            assert_eq!(results[1].get("string"), Ok(&FieldValue::from("Wide")));
+            assert_eq!(results[1].get("integer"), Ok(&FieldValue::from(7)));
+            // B has no string field, but its flag was not read
+            assert!(results[0].is_projected("string"));
+            assert!(!results[0].is_projected("flag"));
+            assert!(results[0].get("flag").unwrap_err().contains("was not read"));
+            // the results of a selecting query continue at the right position
+            let query = database.query()
+                .collections(vec![CollectionSelector::from("test")])
+                .order_by("integer", Ordering::DESCENDING)
+                .select(&["string"]);
+            let mut paginator = Paginator::new(query, 2);
+            assert_eq!(names(paginator.next_page().await.unwrap()), vec!["F", "B"]);
+            assert_eq!(names(paginator.next_page().await.unwrap()), vec!["D", "C"]);
+            let document = database.get_document_masked("test", "F", Some(vec![FieldPath::from("integer")]))
+                .await
+                .unwrap();
+            assert_eq!(document.fields().len(), 1);
+            assert_eq!(document.projection(), Some(&[FieldPath::from("integer")][..]));
+            let document = database.get_document("test", "F").await.unwrap();
+            assert_eq!(document.projection(), None);
+            assert!(document.is_projected("integer"));
+        })
+    }
+    #[test]
+    fn aggregations() {
+        block_on(async {
+            let database = test_database().await;
+            let query = || database.query()
+                .collections(vec![CollectionSelector::from("test")]);
+            assert_eq!(query().count().await.unwrap(), 5);
+            assert_eq!(query().limit(2).count().await.unwrap(), 2);
+            // the selected fields do not limit the aggregated fields
+            assert_eq!(query().select(&["string"]).sum("integer").await.unwrap(), Sum::Integer(19));
+            assert_eq!(query().filter("integer", FilterOp::EQUAL(5)).avg("integer").await.unwrap(),
+                       Some(5.0));
+            assert_eq!(query().avg("string").await.unwrap(), None);
+        })
+    }
+    #[test]
+    fn collection_group() {
+        block_on(async {
+            let database = MemoryDatabase::default();
+            for (path, name) in &[("posts/a/comments", "A1"),
+                                  ("posts/a/comments", "A2"),
+                                  ("posts/b/comments", "B1"),
+                                  ("comments", "C1")] {
+                database.collection(path)
+                    .create_document(Document::builder().name(name).build())
+                    .await
+                    .unwrap();
+            }
+            let results = database.query()
+                .collection_group("comments")
+                .run().await
+                .unwrap();
+            // ordered by their paths
+            assert_eq!(names(results), vec!["C1", "A1", "A2", "B1"]);
+            let results = database.query()
+                .collection_group("comments")
+                .under("posts/a")
+                .run().await
+                .unwrap();
+            assert_eq!(names(results), vec!["A1", "A2"]);
+            let results = database.query()
+                .collections(vec![CollectionSelector::from("comments")])
+                .under("posts/b")
+                .run().await
+                .unwrap();
+            assert_eq!(names(results), vec!["B1"]);
+        })
+    }
+    #[test]
+    fn unbound_query() {
+        block_on(async {
+            let results = MemoryQuery::new()
+                .collections(vec![CollectionSelector::from("test")])
+                .run().await
+                .unwrap();
+            assert!(results.is_empty());
+        })
+    }
+    #[test]
+    fn cursors() {
+        block_on(async {
+            let database = test_database().await;
+            let query = || database.query()
+                .collections(vec![CollectionSelector::from("test")])
+                .order_by("integer", Ordering::ASCENDING);
+            let first = query().limit(2).run().await.unwrap();
+            assert_eq!(names(first.clone()), vec!["A", "C"]);
+            // C and D have the same value, the name decides
+            let next = query().start_after(first.last().unwrap()).run().await.unwrap();
+            assert_eq!(names(next), vec!["D", "B"]);
+            let results = query().end_before(vec![FieldValue::from(6)]).run().await.unwrap();
+            assert_eq!(names(results), vec!["A", "C", "D"]);
+        })
+    }
+    #[test]
+    fn paginator() {
+        block_on(async {
+            let database = test_database().await;
+            let query = database.query()
+                .collections(vec![CollectionSelector::from("test")])
+                .order_by("integer", Ordering::ASCENDING);
+            let mut paginator = Paginator::new(query.clone(), 2);
+            assert_eq!(names(paginator.next_page().await.unwrap()), vec!["A", "C"]);
+            let token = paginator.token().unwrap();
+            // continue from the token, like a later request would
+            let mut paginator = Paginator::from_token(query.clone(), 2, &token).unwrap();
+            assert_eq!(names(paginator.next_page().await.unwrap()), vec!["D", "B"]);
+            assert!(paginator.next_page().await.unwrap().is_empty());
+            assert!(paginator.is_done());
+            assert_eq!(paginator.token(), None);
+            assert!(Paginator::from_token(query.clone(), 2, "invalid").is_err());
+            // tokens only continue the query and page size they were made for
+            let other = query.clone().order_by("string", Ordering::ASCENDING);
+            assert!(Paginator::from_token(other, 2, &token).is_err());
+            assert!(Paginator::from_token(query, 3, &token).is_err());
+            // documents are only skipped before the first page
+            let query = database.query()
+                .collections(vec![CollectionSelector::from("test")])
+                .order_by("integer", Ordering::ASCENDING)
+                .skip(1);
+            let mut paginator = Paginator::new(query.clone(), 2);
+            assert_eq!(names(paginator.next_page().await.unwrap()), vec!["C", "D"]);
+            let token = paginator.token().unwrap();
+            let mut paginator = Paginator::from_token(query, 2, &token).unwrap();
+            assert_eq!(names(paginator.next_page().await.unwrap()), vec!["B"]);
+            assert!(paginator.is_done());
+        })
+    }
+}
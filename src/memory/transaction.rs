@@ -0,0 +1,220 @@
+use crate::{
+    error::DatabaseError,
+    database::Database,
+    document::{
+        Document,
+    },
+    memory::{
+        MemoryDatabase,
+        query::MemoryQuery,
+    },
+    transaction::{
+        Transaction,
+    },
+    write::{
+        Write,
+    },
+};
+
+// MemoryTransactions are optimistic: they remember the update time
+// of every document they read and the results of every query they
+// ran, and their commit is aborted if any of these changed in the
+// meantime. Rerunning the queries also detects documents which
+// were added to the results of a query after it was read
+pub struct MemoryTransaction {
+    database: MemoryDatabase,
+    // document paths with their update times when they
+    // were read, None if the document did not exist
+    pub(crate) reads: Vec<(String, Option<String>)>,
+    // queries with the paths and update times of their results
+    pub(crate) queries: Vec<(MemoryQuery<'static>, Vec<(String, Option<String>)>)>,
+    pub(crate) writes: Vec<Write>,
+}
+
+impl MemoryTransaction {
+    pub(crate) fn new(database: MemoryDatabase) -> Self {
+        Self {
+            database,
+            reads: Vec::new(),
+            queries: Vec::new(),
+            writes: Vec::new(),
+        }
+    }
+}
+
+// the paths and update times of documents, to compare results
+pub(crate) fn versions(documents: &[Document]) -> Vec<(String, Option<String>)> {
+    documents.iter()
+        .map(|d| (d.id().to_string(), d.update_time()))
+        .collect()
+}
+
+impl<'a> Transaction<'a> for MemoryTransaction {
+    type Database = MemoryDatabase;
+
+    fn get_document<A: ToString, B: ToString>(
+        &mut self,
+        collection_id: A,
+        document_id: B,
+        ) -> Result<Document, DatabaseError> {
+        let (collection_id, document_id) = (collection_id.to_string(), document_id.to_string());
+        let path = format!("{}/{}",
+                           self.database.collection_path(&collection_id),
+                           document_id);
+        let result = self.database.read_document(&collection_id, &document_id, None);
+        self.reads.push((path, result.as_ref().ok().and_then(|d| d.update_time())));
+        result
+    }
+    fn run_query(
+        &mut self,
+        query: MemoryQuery<'a>,
+        ) -> Result<Vec<Document>, DatabaseError> {
+        let query = MemoryQuery {
+            database: None,
+            query: query.query,
+        };
+        let documents = {
+            let collections = self.database.collections.read().unwrap();
+            query.results_in(&collections)
+        };
+        self.queries.push((query, versions(&documents)));
+        Ok(documents)
+    }
+    fn write(&mut self, write: Write) {
+        self.writes.push(write);
+    }
+    fn generate_id(&self) -> String {
+        self.database.generate_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        collection::Collection,
+        document::FieldValue,
+        error::DatabaseErrorKind,
+        firestore::query::CollectionSelector,
+        write::SetOptions,
+        query::{
+            Query,
+            filter::{Filter, FilterOp},
+        },
+    };
+    use futures::executor::block_on;
+    use std::convert::TryFrom;
+    use std::sync::{
+        Arc,
+        atomic::{
+            AtomicUsize,
+            Ordering,
+        },
+    };
+
+    #[test]
+    fn retry_on_contention() {
+        let database = MemoryDatabase::default();
+        block_on(database.collection("counters")
+                     .create_document(Document::builder()
+                                          .name("visits")
+                                          .field("count", 0)
+                                          .build()))
+            .unwrap();
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let (other, counter) = (database.clone(), attempts.clone());
+        let count = block_on(database.run_transaction(move |transaction| {
+                let document = transaction.get_document("counters", "visits")?;
+                let count = i64::try_from(document.get("count").unwrap().clone()).unwrap();
+                if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                    // a concurrent write during the first attempt
+                    block_on(other.collection("counters")
+                                 .update_document("visits",
+                                                  Document::builder().field("count", 10).build(),
+                                                  None))?;
+                }
+                transaction.update_document("counters",
+                                            "visits",
+                                            Document::builder().field("count", count + 1).build(),
+                                            None);
+                Ok(count + 1)
+            }))
+            .unwrap();
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(count, 11);
+        let document = block_on(database.get_document("counters", "visits")).unwrap();
+        assert_eq!(document.get("count"), Ok(&FieldValue::from(11)));
+    }
+    #[test]
+    fn error_discards_writes() {
+        let database = MemoryDatabase::default();
+        block_on(database.run_transaction(|transaction| {
+                transaction.create_document("test", Document::builder().name("A").build());
+                transaction.get_document("test", "Missing")
+            }))
+            .expect_err("Read a missing document!");
+        assert!(block_on(database.get_documents("test")).unwrap().is_empty());
+    }
+    #[test]
+    fn runs_on_blocking_thread() {
+        let database = MemoryDatabase::default();
+        let start = std::time::Instant::now();
+        let transaction = database.run_transaction(|_| {
+                std::thread::sleep(std::time::Duration::from_millis(200));
+                Ok(())
+            });
+        // the caller is not blocked until the transaction is done
+        assert!(start.elapsed() < std::time::Duration::from_millis(100));
+        block_on(transaction).unwrap();
+        assert!(start.elapsed() >= std::time::Duration::from_millis(200));
+    }
+    #[test]
+    fn phantom_aborts() {
+        let database = MemoryDatabase::default();
+        block_on(database.collection("test")
+                     .create_document(Document::builder().name("A").field("n", 1).build()))
+            .unwrap();
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let (other, counter) = (database.clone(), attempts.clone());
+        let count = block_on(database.run_transaction(move |transaction| {
+                let query = MemoryQuery::new()
+                    .collections(vec![CollectionSelector::from("test")])
+                    .filter("n", FilterOp::GREATER_THAN(0));
+                let count = transaction.run_query(query)?.len();
+                if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                    // a document enters the results of the query
+                    block_on(other.collection("test")
+                                 .create_document(Document::builder().name("B").field("n", 2).build()))?;
+                }
+                Ok(count)
+            }))
+            .unwrap();
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(count, 2);
+    }
+    #[test]
+    fn generated_ids() {
+        let next = AtomicUsize::new(0);
+        let database = MemoryDatabase::default()
+            .with_id_generator(move || format!("id{}", next.fetch_add(1, Ordering::SeqCst)));
+        block_on(database.run_transaction(|transaction| {
+                transaction.create_document("test", Document::builder().field("a", 1).build());
+                transaction.set_document("test", Document::default(), SetOptions::Overwrite);
+                Ok(())
+            }))
+            .unwrap();
+        let names: Vec<String> = block_on(database.get_documents("test"))
+            .unwrap()
+            .iter()
+            .map(|d| d.name().to_string())
+            .collect();
+        assert_eq!(names, vec!["id0", "id1"]);
+        // writes without a name are rejected instead of writing to the collection
+        let error = block_on(database.commit(vec![Write::Create {
+                collection_id: "test".to_string(),
+                document: Document::default(),
+            }]))
+            .unwrap_err();
+        assert_eq!(error.kind(), DatabaseErrorKind::InvalidArgument);
+    }
+}
@@ -0,0 +1,389 @@
+use crate::{
+    database::Database,
+    error::{
+        DatabaseError,
+        DatabaseErrorKind,
+    },
+    document::{
+        Document,
+        FieldValue,
+        FieldPath,
+    },
+    memory::{
+        MemoryDatabase,
+        Collections,
+        DocumentMap,
+        timestamp,
+    },
+    query::eval::{
+        self,
+        field_value,
+    },
+    write::{
+        Write,
+        FieldTransform,
+        Precondition,
+        SetOptions,
+    },
+};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+fn is_number(v: &FieldValue) -> bool {
+    v.0.integer_value.is_some() || v.0.double_value.is_some()
+}
+fn as_f64(v: &FieldValue) -> f64 {
+    f64::try_from(v.clone())
+        .or_else(|_| i64::try_from(v.clone()).map(|i| i as f64))
+        .unwrap_or(0.0)
+}
+fn array_values(v: Option<FieldValue>) -> Vec<FieldValue> {
+    v.and_then(|v| v.0.array_value)
+     .and_then(|a| a.values)
+     .unwrap_or_default()
+     .into_iter()
+     .map(FieldValue::from)
+     .collect()
+}
+
+// computes the new value of a field from its current value
+fn transformed(current: Option<FieldValue>, transform: FieldTransform, now: &str) -> FieldValue {
+    let current = current.filter(|v| match &transform {
+        FieldTransform::Increment(..) |
+        FieldTransform::Maximum(..) |
+        FieldTransform::Minimum(..) => is_number(v),
+        _ => true,
+    });
+    match transform {
+        FieldTransform::SetToServerValue(..) => FieldValue::timestamp_value(now),
+        FieldTransform::Increment(_, operand) => match current {
+            Some(current) => match (i64::try_from(current.clone()),
+                                    i64::try_from(operand.clone())) {
+                (Ok(a), Ok(b)) => FieldValue::from(a.saturating_add(b)),
+                _ => FieldValue::from(as_f64(&current) + as_f64(&operand)),
+            },
+            None => operand,
+        },
+        FieldTransform::Maximum(_, operand) => match current {
+            Some(current) if eval::compare(&current, &operand) != Ordering::Less => current,
+            _ => operand,
+        },
+        FieldTransform::Minimum(_, operand) => match current {
+            Some(current) if eval::compare(&current, &operand) != Ordering::Greater => current,
+            _ => operand,
+        },
+        FieldTransform::AppendMissingElements(_, elements) => {
+            let mut values = array_values(current);
+            for element in elements {
+                if !values.iter().any(|v| eval::equal(v, &element)) {
+                    values.push(element);
+                }
+            }
+            FieldValue::from(values)
+        },
+        FieldTransform::RemoveAllFromArray(_, elements) => {
+            let values: Vec<FieldValue> = array_values(current)
+                .into_iter()
+                .filter(|v| !elements.iter().any(|e| eval::equal(v, e)))
+                .collect();
+            FieldValue::from(values)
+        },
+    }
+}
+
+pub(crate) fn apply_transform(document: &mut Document, transform: FieldTransform, now: &str) {
+    let path = transform.field_path().clone();
+    let current = field_value(document, path.as_str());
+    document.set_field(&path, transformed(current, transform, now));
+}
+
+// fails if the current document does not meet the precondition,
+// with the errors Firestore returns
+fn check(precondition: &Precondition, current: Option<&Document>, path: &str) -> Result<(), DatabaseError> {
+    match (precondition, current) {
+        (Precondition::Exists(true), None) => Err(DatabaseError::new(
+                DatabaseErrorKind::NotFound,
+                format!("No document to update: {}", path))),
+        (Precondition::Exists(false), Some(_)) => Err(DatabaseError::new(
+                DatabaseErrorKind::AlreadyExists,
+                format!("Document already exists: {}", path))),
+        (Precondition::UpdateTime(time), current) =>
+            if current.and_then(|d| d.update_time()).as_ref() == Some(time) {
+                Ok(())
+            } else {
+                Err(DatabaseError::new(
+                    DatabaseErrorKind::PreconditionFailed,
+                    format!("Document {} was not last updated at {}", path, time)))
+            },
+        _ => Ok(()),
+    }
+}
+
+// the current document with the masked fields of the update.
+// Masked fields missing in the update are deleted
+fn merged(mut current: Document, update: &Document, mask: &[FieldPath]) -> Document {
+    for field in mask {
+        match field_value(update, field.as_str()) {
+            Some(value) => current.set_field(field, value),
+            None => current.remove_field(field),
+        }
+    }
+    current
+}
+
+// a document as it is stored, with its full path and timestamps
+fn stored(path: String, document: Document, create_time: Option<String>, update_time: String) -> Document {
+    Document::from(google_firestore::Document {
+        name: Some(path),
+        create_time,
+        update_time: Some(update_time),
+        ..document.into()
+    })
+}
+
+impl MemoryDatabase {
+    // applies a single write to the collections
+    pub(crate) fn apply(&self, collections: &mut Collections, write: Write) -> Result<(), DatabaseError> {
+        write.check_document_id()?;
+        let path = format!("{}/{}",
+                           self.collection_path(write.collection_id()),
+                           write.document_id());
+        let document_id = write.document_id().to_string();
+        let documents = collections.entry(write.collection_id().to_string())
+                                   .or_insert_with(DocumentMap::new);
+        let now = timestamp();
+        match write {
+            Write::Create { document, .. } => {
+                check(&Precondition::Exists(false), documents.get(&document_id), &path)?;
+                documents.insert(document_id, stored(path, document, Some(now.clone()), now));
+            },
+            Write::Update { document, mask, transforms, precondition, .. } => {
+                let current = documents.get(&document_id);
+                check(&precondition, current, &path)?;
+                let create_time = current.map(|d| d.create_time())
+                                         .unwrap_or(Some(now.clone()));
+                let mut updated = match mask {
+                    Some(mask) => merged(current.cloned().unwrap_or_default(), &document, &mask),
+                    None => document,
+                };
+                for transform in transforms {
+                    apply_transform(&mut updated, transform, &now);
+                }
+                documents.insert(document_id, stored(path, updated, create_time, now));
+            },
+            Write::Set { document, options, .. } => {
+                let current = documents.get(&document_id);
+                let create_time = current.map(|d| d.create_time())
+                                         .unwrap_or(Some(now.clone()));
+                let updated = match options.mask(&document) {
+                    Some(mask) => merged(current.cloned().unwrap_or_default(), &document, &mask),
+                    None => document,
+                };
+                documents.insert(document_id, stored(path, updated, create_time, now));
+            },
+            Write::Delete { precondition, .. } => {
+                if let Some(precondition) = &precondition {
+                    check(precondition, documents.get(&document_id), &path)?;
+                }
+                // like Firestore, deleting a missing document is not an error
+                documents.remove(&document_id);
+            },
+            Write::Transform { transforms, .. } => {
+                let (mut document, create_time) = match documents.get(&document_id) {
+                    Some(current) => (current.clone(), current.create_time()),
+                    None => (Document::default(), Some(now.clone())),
+                };
+                for transform in transforms {
+                    apply_transform(&mut document, transform, &now);
+                }
+                documents.insert(document_id, stored(path, document, create_time, now));
+            },
+        }
+        Ok(())
+    }
+    // applies all writes or none of them
+    pub(crate) fn apply_all(&self, collections: &mut Collections, writes: Vec<Write>) -> Result<(), DatabaseError> {
+        let mut staged = collections.clone();
+        for write in writes {
+            self.apply(&mut staged, write)?;
+        }
+        *collections = staged;
+        Ok(())
+    }
+    // the collection and document id of a full document path
+    pub(crate) fn locate<'p>(&self, path: &'p str) -> Option<(&'p str, &'p str)> {
+        let root = format!("{}/", self.get_path());
+        if !path.starts_with(&root) {
+            return None;
+        }
+        let mut parts = path[root.len()..].rsplitn(2, "/");
+        let document_id = parts.next()?;
+        let collection_id = parts.next()?;
+        Some((collection_id, document_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        collection::Collection,
+        document::FieldPath,
+        write::ServerValue,
+    };
+    use futures::executor::block_on;
+    use std::collections::HashMap;
+
+    #[test]
+    fn batch_is_atomic() {
+        block_on(async {
+            let database = MemoryDatabase::default();
+            database.collection("test")
+                .create_document(Document::builder().name("A").build())
+                .await
+                .unwrap();
+            database.batch()
+                .create("test", Document::builder().name("B").build())
+                .create("test", Document::builder().name("A").build())
+                .commit()
+                .await
+                .expect_err("Committed a batch with a conflicting create!");
+            let names: Vec<String> = database.get_documents("test")
+                .await
+                .unwrap()
+                .iter()
+                .map(|d| d.name().to_string())
+                .collect();
+            assert_eq!(names, vec!["A"]);
+            database.batch()
+                .create("test", Document::builder().name("B").build())
+                .delete("test", "A")
+                .create("other", Document::builder().name("C").build())
+                .commit()
+                .await
+                .unwrap();
+            assert_eq!(database.get_documents("test").await.unwrap().len(), 1);
+            assert_eq!(database.get_documents("other").await.unwrap().len(), 1);
+        })
+    }
+    #[test]
+    fn transforms() {
+        block_on(async {
+            let database = MemoryDatabase::default();
+            database.collection("test")
+                .create_document(Document::builder()
+                                     .name("A")
+                                     .field("count", 1)
+                                     .field("ratio", 0.5)
+                                     .field("tags", vec![FieldValue::from("a"), FieldValue::from("b")])
+                                     .build())
+                .await
+                .unwrap();
+            database.batch()
+                .transform("test", "A", vec![
+                    FieldTransform::Increment(FieldPath::from("count"), FieldValue::from(2)),
+                    FieldTransform::Increment(FieldPath::from("ratio"), FieldValue::from(1)),
+                    FieldTransform::Maximum(FieldPath::from("max"), FieldValue::from(3)),
+                    FieldTransform::AppendMissingElements(FieldPath::from("tags"),
+                                                          vec![FieldValue::from("b"), FieldValue::from("c")]),
+                    FieldTransform::RemoveAllFromArray(FieldPath::from("tags"),
+                                                       vec![FieldValue::from("a")]),
+                    FieldTransform::SetToServerValue(FieldPath::from("seen.at"), ServerValue::REQUEST_TIME),
+                ])
+                .commit()
+                .await
+                .unwrap();
+            let document = database.get_document("test", "A").await.unwrap();
+            assert_eq!(document.get("count"), Ok(&FieldValue::from(3)));
+            assert_eq!(document.get("ratio"), Ok(&FieldValue::from(1.5)));
+            assert_eq!(document.get("max"), Ok(&FieldValue::from(3)));
+            assert_eq!(document.get("tags"),
+                       Ok(&FieldValue::from(vec![FieldValue::from("b"), FieldValue::from("c")])));
+            assert!(field_value(&document, "seen.at")
+                        .and_then(|v| v.0.timestamp_value)
+                        .is_some());
+        })
+    }
+    #[test]
+    fn update_transforms() {
+        block_on(async {
+            let database = MemoryDatabase::default();
+            let collection = database.collection("test");
+            collection.create_document(Document::builder()
+                                           .name("A")
+                                           .field("count", 1)
+                                           .build())
+                .await
+                .unwrap();
+            database.batch()
+                .update_with_transforms("test", "A",
+                                        Document::builder().field("name", "a").build(),
+                                        Some(vec![FieldPath::from("name")]),
+                                        vec![FieldTransform::increment("count", 1),
+                                             FieldTransform::minimum("low", 2.5)])
+                .commit()
+                .await
+                .unwrap();
+            collection.transform_document("A", vec![
+                    FieldTransform::append_missing_elements("tags", vec!["a", "b"]),
+                    FieldTransform::remove_all_from_array("tags", vec!["a"]),
+                    FieldTransform::set_to_server_value("seen", ServerValue::REQUEST_TIME),
+                ])
+                .await
+                .unwrap();
+            let document = collection.get_document("A").await.unwrap();
+            assert_eq!(document.get("name"), Ok(&FieldValue::from("a")));
+            assert_eq!(document.get("count"), Ok(&FieldValue::from(2)));
+            assert_eq!(document.get("low"), Ok(&FieldValue::from(2.5)));
+            assert_eq!(document.get("tags"), Ok(&FieldValue::from(vec![FieldValue::from("b")])));
+            assert!(field_value(&document, "seen")
+                        .and_then(|v| v.0.timestamp_value)
+                        .is_some());
+            // transforms create missing documents
+            collection.transform_document("B", vec![FieldTransform::maximum("high", 3)])
+                .await
+                .unwrap();
+            assert_eq!(collection.get_document("B").await.unwrap().get("high"),
+                       Ok(&FieldValue::from(3)));
+        })
+    }
+    #[test]
+    fn set_document() {
+        block_on(async {
+            let database = MemoryDatabase::default();
+            let collection = database.collection("test");
+            let address = |fields: &[(&str, &str)]| FieldValue::from(fields.iter()
+                .map(|(k, v)| (k.to_string(), FieldValue::from(*v)))
+                .collect::<HashMap<String, FieldValue>>());
+            let document = Document::builder()
+                .name("A")
+                .field("count", 1)
+                .field("address", address(&[("city", "Berlin"), ("zip", "10115")]))
+                .build();
+            // setting the same document repeatedly is not an error
+            collection.set_document(document.clone(), SetOptions::Overwrite).await.unwrap();
+            collection.set_document(document.clone(), SetOptions::Overwrite).await.unwrap();
+            let city = |city: &str| Document::builder()
+                .name("A")
+                .field("address", address(&[("city", city)]))
+                .build();
+            collection.set_document(city("Hamburg"), SetOptions::Merge).await.unwrap();
+            let merged = collection.get_document("A").await.unwrap();
+            assert_eq!(merged.get("count"), Ok(&FieldValue::from(1)));
+            assert_eq!(field_value(&merged, "address.city"), Some(FieldValue::from("Hamburg")));
+            assert_eq!(field_value(&merged, "address.zip"), Some(FieldValue::from("10115")));
+            // masked fields missing in the document are deleted
+            collection.set_document(city("Munich"),
+                                    SetOptions::MergeFields(vec![FieldPath::from("address"),
+                                                                 FieldPath::from("count")]))
+                .await
+                .unwrap();
+            let merged = collection.get_document("A").await.unwrap();
+            assert!(merged.get("count").is_err());
+            assert_eq!(field_value(&merged, "address.zip"), None);
+            assert_eq!(field_value(&merged, "address.city"), Some(FieldValue::from("Munich")));
+            collection.set_document(city("Bonn"), SetOptions::Overwrite).await.unwrap();
+            assert_eq!(collection.get_document("A").await.unwrap().fields(), city("Bonn").fields());
+        })
+    }
+}
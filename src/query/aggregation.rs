@@ -0,0 +1,121 @@
+use crate::{
+    database::DatabaseFuture,
+    document::{
+        Document,
+        FieldValue,
+    },
+    error::{
+        DatabaseError,
+        DatabaseErrorKind,
+    },
+    query::eval,
+};
+use std::convert::TryFrom;
+
+// Aggregations compute a single result over the documents
+// matching a query, without reading the documents.
+// Limit and skip of the query are applied before aggregating
+pub trait Aggregate {
+    // the number of matching documents
+    fn count(self) -> DatabaseFuture<u64>;
+    // the sum of the numeric values of the field,
+    // documents without a number in the field are ignored
+    fn sum(self, field: &str) -> DatabaseFuture<Sum>;
+    // the average of the numeric values of the field,
+    // None if no document has a number in the field
+    fn avg(self, field: &str) -> DatabaseFuture<Option<f64>>;
+}
+
+// a sum stays an integer unless a double
+// was added or the sum overflowed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sum {
+    Integer(i64),
+    Double(f64),
+}
+
+impl Sum {
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            Sum::Integer(i) => *i as f64,
+            Sum::Double(d) => *d,
+        }
+    }
+    fn add(self, value: &FieldValue) -> Self {
+        match (self, i64::try_from(value.clone())) {
+            (Sum::Integer(sum), Ok(i)) => sum.checked_add(i)
+                                             .map(Sum::Integer)
+                                             .unwrap_or(Sum::Double(sum as f64 + i as f64)),
+            (sum, _) => Sum::Double(sum.as_f64() + as_f64(value)),
+        }
+    }
+}
+
+impl Default for Sum {
+    fn default() -> Self {
+        Sum::Integer(0)
+    }
+}
+
+impl TryFrom<FieldValue> for Sum {
+    type Error = DatabaseError;
+    fn try_from(value: FieldValue) -> Result<Self, Self::Error> {
+        i64::try_from(value.clone())
+            .map(Sum::Integer)
+            .or_else(|_| f64::try_from(value.clone()).map(Sum::Double))
+            .map_err(|_| DatabaseError::new(
+                    DatabaseErrorKind::Decode,
+                    format!("Expected a sum, got {:?}", value)))
+    }
+}
+
+fn as_f64(value: &FieldValue) -> f64 {
+    f64::try_from(value.clone())
+        .or_else(|_| i64::try_from(value.clone()).map(|i| i as f64))
+        .unwrap_or(0.0)
+}
+// the numbers in the field of the documents
+fn numbers<'d>(documents: &'d [Document], field: &'d str) -> impl Iterator<Item=FieldValue> + 'd {
+    documents.iter()
+        .filter_map(move |d| eval::field_value(d, field))
+        .filter(|v| v.0.integer_value.is_some() || v.0.double_value.is_some())
+}
+
+// aggregations of local documents, computed like Firestore does
+pub(crate) fn sum(documents: &[Document], field: &str) -> Sum {
+    numbers(documents, field).fold(Sum::default(), |sum, v| sum.add(&v))
+}
+pub(crate) fn avg(documents: &[Document], field: &str) -> Option<f64> {
+    let (total, count) = numbers(documents, field)
+        .fold((0.0, 0), |(total, count), v| (total + as_f64(&v), count + 1));
+    if count == 0 {
+        None
+    } else {
+        Some(total / count as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn documents() -> Vec<Document> {
+        vec![
+            Document::builder().name("A").field("n", 1).build(),
+            Document::builder().name("B").field("n", 2).build(),
+            Document::builder().name("C").field("n", "text").build(),
+            Document::builder().name("D").build(),
+        ]
+    }
+    #[test]
+    fn sum_avg() {
+        let mut documents = documents();
+        assert_eq!(sum(&documents, "n"), Sum::Integer(3));
+        assert_eq!(avg(&documents, "n"), Some(1.5));
+        assert_eq!(avg(&documents, "missing"), None);
+        documents.push(Document::builder().name("E").field("n", 0.5).build());
+        assert_eq!(sum(&documents, "n"), Sum::Double(3.5));
+        // overflowing integer sums become doubles
+        assert_eq!(Sum::Integer(3).add(&FieldValue::from(i64::MAX)),
+                   Sum::Double(3.0 + i64::MAX as f64));
+    }
+}
@@ -0,0 +1,60 @@
+use crate::document::{
+    Document,
+    FieldValue,
+};
+use crate::query::eval;
+use google_firestore::Order;
+
+// A position in the results of a query, given either by the values
+// of the fields the query is ordered by, or by a document whose
+// values of these fields are used
+#[derive(Clone, Debug)]
+pub enum Cursor {
+    Values(Vec<FieldValue>),
+    Document(Document),
+}
+
+impl Cursor {
+    // the values of the cursor for a query with these orders.
+    // For a document these include its name, which Firestore
+    // orders by after all other orders
+    pub(crate) fn values(&self, orders: &[Order]) -> Vec<FieldValue> {
+        match self {
+            Cursor::Values(values) => values.clone(),
+            Cursor::Document(document) => orders.iter()
+                .filter_map(|order| order.field.as_ref()?.field_path.as_ref())
+                .map(|path| eval::field_value(document, path)
+                                .unwrap_or_else(FieldValue::null_value))
+                .collect(),
+        }
+    }
+    // the cursor in the format of a structured query. Before
+    // means the position is before the values of the cursor
+    pub(crate) fn to_firestore(&self, orders: &[Order], before: bool) -> google_firestore::Cursor {
+        google_firestore::Cursor {
+            values: Some(self.values(orders)
+                             .into_iter()
+                             .map(|v| v.0)
+                             .collect()),
+            before: Some(before),
+        }
+    }
+}
+
+impl From<Vec<FieldValue>> for Cursor {
+    fn from(values: Vec<FieldValue>) -> Self {
+        Cursor::Values(values)
+    }
+}
+
+impl From<Document> for Cursor {
+    fn from(document: Document) -> Self {
+        Cursor::Document(document)
+    }
+}
+
+impl From<&Document> for Cursor {
+    fn from(document: &Document) -> Self {
+        Cursor::Document(document.clone())
+    }
+}
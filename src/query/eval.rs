@@ -0,0 +1,652 @@
+use crate::document::{
+    Document,
+    FieldValue,
+    FieldPath,
+};
+use google_firestore::{
+    Value,
+    Filter,
+    FieldFilter,
+    UnaryFilter,
+    Order,
+    Cursor,
+};
+use std::cmp::Ordering;
+
+// The evaluator applies the filter, orders, limit and offset of a
+// structured query to documents locally, returning what Firestore
+// would return for the same query on those documents.
+
+// value types in the order in which Firestore sorts them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum TypeOrder {
+    Null,
+    Boolean,
+    NaN,
+    Number,
+    Timestamp,
+    String,
+    Bytes,
+    Reference,
+    GeoPoint,
+    Array,
+    Map,
+}
+
+fn type_order(v: &Value) -> TypeOrder {
+    if v.boolean_value.is_some() {
+        TypeOrder::Boolean
+    } else if v.integer_value.is_some() {
+        TypeOrder::Number
+    } else if let Some(double) = v.double_value {
+        if double.is_nan() {
+            TypeOrder::NaN
+        } else {
+            TypeOrder::Number
+        }
+    } else if v.timestamp_value.is_some() {
+        TypeOrder::Timestamp
+    } else if v.string_value.is_some() {
+        TypeOrder::String
+    } else if v.bytes_value.is_some() {
+        TypeOrder::Bytes
+    } else if v.reference_value.is_some() {
+        TypeOrder::Reference
+    } else if v.geo_point_value.is_some() {
+        TypeOrder::GeoPoint
+    } else if v.array_value.is_some() {
+        TypeOrder::Array
+    } else if v.map_value.is_some() {
+        TypeOrder::Map
+    } else {
+        // FieldValue::null_value() sets no field at all
+        TypeOrder::Null
+    }
+}
+
+fn as_f64(v: &Value) -> f64 {
+    v.double_value
+     .or_else(|| v.integer_value
+                  .as_ref()
+                  .and_then(|i| i.parse::<i64>().ok())
+                  .map(|i| i as f64))
+     .unwrap_or(0.0)
+}
+
+fn compare_numbers(a: &Value, b: &Value) -> Ordering {
+    match (&a.integer_value, &b.integer_value) {
+        (Some(x), Some(y)) => x.parse::<i64>().unwrap_or(0)
+                               .cmp(&y.parse::<i64>().unwrap_or(0)),
+        _ => as_f64(a).partial_cmp(&as_f64(b)).unwrap_or(Ordering::Equal),
+    }
+}
+
+fn compare_timestamps(a: &str, b: &str) -> Ordering {
+    match (chrono::DateTime::parse_from_rfc3339(a),
+           chrono::DateTime::parse_from_rfc3339(b)) {
+        (Ok(x), Ok(y)) => x.cmp(&y),
+        _ => a.cmp(b),
+    }
+}
+
+// decodes standard or url safe base64, as bytes values are sent
+fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(s.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in s.trim_end_matches('=').bytes() {
+        let digit = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | digit as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+// bytes are ordered by their decoded bytes,
+// the base64 alphabet is not in byte order
+fn compare_bytes(a: &str, b: &str) -> Ordering {
+    match (decode_base64(a), decode_base64(b)) {
+        (Some(x), Some(y)) => x.cmp(&y),
+        _ => a.cmp(b),
+    }
+}
+
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    let (ta, tb) = (type_order(a), type_order(b));
+    if ta != tb {
+        return ta.cmp(&tb);
+    }
+    match ta {
+        TypeOrder::Null |
+        TypeOrder::NaN => Ordering::Equal,
+        TypeOrder::Boolean => a.boolean_value.cmp(&b.boolean_value),
+        TypeOrder::Number => compare_numbers(a, b),
+        TypeOrder::Timestamp => compare_timestamps(
+            a.timestamp_value.as_ref().unwrap(),
+            b.timestamp_value.as_ref().unwrap()),
+        TypeOrder::String => a.string_value.cmp(&b.string_value),
+        TypeOrder::Bytes => compare_bytes(
+            a.bytes_value.as_ref().unwrap(),
+            b.bytes_value.as_ref().unwrap()),
+        TypeOrder::Reference =>
+            a.reference_value.as_ref().unwrap().split("/")
+             .cmp(b.reference_value.as_ref().unwrap().split("/")),
+        TypeOrder::GeoPoint => {
+            let (x, y) = (a.geo_point_value.as_ref().unwrap(),
+                          b.geo_point_value.as_ref().unwrap());
+            x.latitude.partial_cmp(&y.latitude)
+             .unwrap_or(Ordering::Equal)
+             .then(x.longitude.partial_cmp(&y.longitude)
+                              .unwrap_or(Ordering::Equal))
+        },
+        TypeOrder::Array => {
+            let empty = Vec::new();
+            let x = a.array_value.as_ref().and_then(|a| a.values.as_ref()).unwrap_or(&empty);
+            let y = b.array_value.as_ref().and_then(|a| a.values.as_ref()).unwrap_or(&empty);
+            x.iter()
+             .zip(y.iter())
+             .map(|(x, y)| compare_values(x, y))
+             .find(|ord| *ord != Ordering::Equal)
+             .unwrap_or_else(|| x.len().cmp(&y.len()))
+        },
+        TypeOrder::Map => {
+            let sorted = |v: &Value| {
+                let mut entries: Vec<(String, Value)> =
+                    v.map_value.as_ref()
+                     .and_then(|m| m.fields.clone())
+                     .unwrap_or_default()
+                     .into_iter()
+                     .collect();
+                entries.sort_by(|(k, _), (l, _)| k.cmp(l));
+                entries
+            };
+            let (x, y) = (sorted(a), sorted(b));
+            x.iter()
+             .zip(y.iter())
+             .map(|((k, v), (l, w))| k.cmp(l).then_with(|| compare_values(v, w)))
+             .find(|ord| *ord != Ordering::Equal)
+             .unwrap_or_else(|| x.len().cmp(&y.len()))
+        },
+    }
+}
+
+// compares two values following Firestore's ordering
+// of values, including values of different types
+pub fn compare(a: &FieldValue, b: &FieldValue) -> Ordering {
+    compare_values(&a.0, &b.0)
+}
+
+// true if both values are of the same type and equal,
+// integers and doubles with the same value are equal
+pub fn equal(a: &FieldValue, b: &FieldValue) -> bool {
+    type_order(&a.0) == type_order(&b.0) &&
+    compare_values(&a.0, &b.0) == Ordering::Equal
+}
+
+// true for null values, whether they were built with
+// FieldValue::null_value() or read from Firestore
+pub fn is_null(value: &FieldValue) -> bool {
+    type_order(&value.0) == TypeOrder::Null
+}
+
+// reads the value at a field path (e.g. "a.b.c") from
+// a document. "__name__" refers to the document's name
+pub fn field_value(document: &Document, field_path: &str) -> Option<FieldValue> {
+    if field_path == "__name__" {
+        return Some(FieldValue::reference_value(document.id()));
+    }
+    let segments = FieldPath::new(field_path).segments();
+    let (first, rest) = segments.split_first()?;
+    let mut value = &document.fields().get(first)?.0;
+    for segment in rest {
+        value = value.map_value.as_ref()?
+                     .fields.as_ref()?
+                     .get(segment)?;
+    }
+    Some(FieldValue(value.clone()))
+}
+
+fn field_path(field: &Option<google_firestore::FieldReference>) -> Option<&str> {
+    field.as_ref()
+         .and_then(|f| f.field_path.as_ref())
+         .map(|p| p.as_str())
+}
+
+fn is_inequality(op: &str) -> bool {
+    match op {
+        "LESS_THAN" |
+        "LESS_THAN_OR_EQUAL" |
+        "GREATER_THAN" |
+        "GREATER_THAN_OR_EQUAL" |
+        "NOT_EQUAL" |
+        "NOT_IN" => true,
+        _ => false,
+    }
+}
+
+// equality only holds for values of the same type
+fn values_equal(a: &Value, b: &Value) -> bool {
+    type_order(a) == type_order(b) &&
+    compare_values(a, b) == Ordering::Equal
+}
+
+// the elements of an array value, empty for other values
+fn array_values(value: &Value) -> &[Value] {
+    value.array_value
+         .as_ref()
+         .and_then(|a| a.values.as_ref())
+         .map(|values| values.as_slice())
+         .unwrap_or(&[])
+}
+
+fn matches_field_filter(filter: &FieldFilter, document: &Document) -> bool {
+    let (value, operand) = match (field_path(&filter.field)
+                                      .and_then(|p| field_value(document, p)),
+                                  &filter.value) {
+        (Some(value), Some(operand)) => (value.0, operand),
+        _ => return false,
+    };
+    let op = filter.op.as_ref().map(|op| op.as_str()).unwrap_or("");
+    // comparisons only match values of the same type
+    let comparable = type_order(&value) == type_order(operand);
+    let ord = compare_values(&value, operand);
+    match op {
+        "EQUAL" => comparable && ord == Ordering::Equal,
+        "LESS_THAN" => comparable && ord == Ordering::Less,
+        "LESS_THAN_OR_EQUAL" => comparable && ord != Ordering::Greater,
+        "GREATER_THAN" => comparable && ord == Ordering::Greater,
+        "GREATER_THAN_OR_EQUAL" => comparable && ord != Ordering::Less,
+        // null values never match the negated operators
+        "NOT_EQUAL" => type_order(&value) != TypeOrder::Null &&
+                       !values_equal(&value, operand),
+        "ARRAY_CONTAINS" =>
+            array_values(&value)
+                 .iter()
+                 .any(|v| values_equal(v, operand)),
+        "IN" =>
+            array_values(operand)
+                .iter()
+                .any(|v| values_equal(&value, v)),
+        "NOT_IN" => type_order(&value) != TypeOrder::Null &&
+                    !array_values(operand)
+                        .iter()
+                        .any(|v| values_equal(&value, v)),
+        "ARRAY_CONTAINS_ANY" =>
+            array_values(&value)
+                .iter()
+                .any(|v| array_values(operand)
+                             .iter()
+                             .any(|o| values_equal(v, o))),
+        _ => false,
+    }
+}
+
+fn matches_unary_filter(filter: &UnaryFilter, document: &Document) -> bool {
+    let value = match field_path(&filter.field)
+                          .and_then(|p| field_value(document, p)) {
+        Some(value) => value.0,
+        None => return false,
+    };
+    match filter.op.as_ref().map(|op| op.as_str()).unwrap_or("") {
+        "IS_NULL" => type_order(&value) == TypeOrder::Null,
+        "IS_NOT_NULL" => type_order(&value) != TypeOrder::Null,
+        "IS_NAN" => type_order(&value) == TypeOrder::NaN,
+        "IS_NOT_NAN" => type_order(&value) != TypeOrder::NaN,
+        _ => false,
+    }
+}
+
+// true if the document passes the filter.
+// An empty filter matches every document
+pub fn matches(filter: &Filter, document: &Document) -> bool {
+    if let Some(composite) = &filter.composite_filter {
+        let empty = Vec::new();
+        let filters = composite.filters.as_ref().unwrap_or(&empty);
+        match composite.op.as_ref().map(|op| op.as_str()) {
+            Some("OR") => filters.iter().any(|f| matches(f, document)),
+            _ => filters.iter().all(|f| matches(f, document)),
+        }
+    } else if let Some(field_filter) = &filter.field_filter {
+        matches_field_filter(field_filter, document)
+    } else if let Some(unary_filter) = &filter.unary_filter {
+        matches_unary_filter(unary_filter, document)
+    } else {
+        true
+    }
+}
+
+fn inequality_fields(filter: &Filter, fields: &mut Vec<String>) {
+    if let Some(composite) = &filter.composite_filter {
+        for f in composite.filters.iter().flatten() {
+            inequality_fields(f, fields);
+        }
+    } else if let Some(field_filter) = &filter.field_filter {
+        if let (Some(path), Some(op)) = (field_path(&field_filter.field),
+                                         field_filter.op.as_ref()) {
+            if is_inequality(op) && !fields.iter().any(|f| f == path) {
+                fields.push(path.to_string());
+            }
+        }
+    }
+}
+
+// the complete ordering Firestore applies for a query:
+// the explicit orders, followed by implicit orders on fields
+// with inequality filters and finally the document name
+pub fn effective_orders(filter: &Filter, orders: &[Order]) -> Vec<Order> {
+    let mut effective = orders.to_vec();
+    let direction = orders.last()
+                          .and_then(|o| o.direction.clone())
+                          .unwrap_or("ASCENDING".to_string());
+    let mut fields = Vec::new();
+    inequality_fields(filter, &mut fields);
+    fields.push("__name__".to_string());
+    for field in fields {
+        if !effective.iter().any(|o| field_path(&o.field) == Some(field.as_str())) {
+            effective.push(Order {
+                field: Some(google_firestore::FieldReference {
+                    field_path: Some(field),
+                }),
+                direction: Some(direction.clone()),
+            });
+        }
+    }
+    effective
+}
+
+// compares documents by the given orders
+pub fn compare_documents(orders: &[Order], a: &Document, b: &Document) -> Ordering {
+    orders.iter()
+        .map(|order| {
+            let ord = match field_path(&order.field) {
+                Some(path) => match (field_value(a, path), field_value(b, path)) {
+                    (Some(x), Some(y)) => compare(&x, &y),
+                    (x, y) => x.is_some().cmp(&y.is_some()),
+                },
+                None => Ordering::Equal,
+            };
+            match order.direction.as_ref().map(|d| d.as_str()) {
+                Some("DESCENDING") => ord.reverse(),
+                _ => ord,
+            }
+        })
+        .find(|ord| *ord != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
+
+// compares a document to the position of a cursor, using
+// the first orders, one for each value of the cursor
+fn compare_to_cursor(orders: &[Order], document: &Document, cursor: &Cursor) -> Ordering {
+    let empty = Vec::new();
+    orders.iter()
+        .zip(cursor.values.as_ref().unwrap_or(&empty))
+        .map(|(order, value)| {
+            let ord = match field_path(&order.field)
+                                .and_then(|p| field_value(document, p)) {
+                Some(v) => compare_values(&v.0, value),
+                None => Ordering::Less,
+            };
+            match order.direction.as_ref().map(|d| d.as_str()) {
+                Some("DESCENDING") => ord.reverse(),
+                _ => ord,
+            }
+        })
+        .find(|ord| *ord != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
+
+// true if the document is not before the start cursor. A cursor
+// which is "before" its position includes documents at it
+fn after_start(orders: &[Order], document: &Document, cursor: &Cursor) -> bool {
+    match compare_to_cursor(orders, document, cursor) {
+        Ordering::Greater => true,
+        Ordering::Equal => cursor.before.unwrap_or(false),
+        Ordering::Less => false,
+    }
+}
+
+// true if the document is not after the end cursor
+fn before_end(orders: &[Order], document: &Document, cursor: &Cursor) -> bool {
+    match compare_to_cursor(orders, document, cursor) {
+        Ordering::Less => true,
+        Ordering::Equal => !cursor.before.unwrap_or(false),
+        Ordering::Greater => false,
+    }
+}
+
+// filters, sorts and pages the documents like Firestore
+// would for a query with these parameters.
+// A limit of 0 means no limit
+pub fn evaluate<I: IntoIterator<Item=Document>>(
+    filter: &Filter,
+    orders: &[Order],
+    start_at: Option<&Cursor>,
+    end_at: Option<&Cursor>,
+    limit: u32,
+    skip: u32,
+    documents: I,
+    ) -> Vec<Document> {
+    let orders = effective_orders(filter, orders);
+    let mut results: Vec<Document> = documents.into_iter()
+        .filter(|d| matches(filter, d))
+        // ordering by a field excludes documents without it
+        .filter(|d| orders.iter()
+                          .all(|o| field_path(&o.field)
+                                       .and_then(|p| field_value(d, p))
+                                       .is_some()))
+        .collect();
+    results.sort_by(|a, b| compare_documents(&orders, a, b));
+    let results = results.into_iter()
+        .filter(|d| start_at.map(|c| after_start(&orders, d, c)).unwrap_or(true))
+        .filter(|d| end_at.map(|c| before_end(&orders, d, c)).unwrap_or(true))
+        .skip(skip as usize);
+    if limit == 0 {
+        results.collect()
+    } else {
+        results.take(limit as usize).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{
+        Document,
+    };
+    use google_firestore::{
+        FieldReference,
+        CompositeFilter,
+    };
+
+    fn field_filter<T: Into<FieldValue>>(field: &str, op: &str, value: T) -> Filter {
+        Filter {
+            field_filter: Some(FieldFilter {
+                field: Some(FieldReference {
+                    field_path: Some(field.to_string()),
+                }),
+                op: Some(op.to_string()),
+                value: Some(value.into().0),
+            }),
+            ..Filter::default()
+        }
+    }
+    fn test_documents() -> Vec<Document> {
+        vec![
+            Document::builder().name("LessThan").field("integer", 3).build(),
+            Document::builder().name("GreaterThan").field("integer", 6).build(),
+            Document::builder().name("LessThanOrEqual").field("integer", 5).build(),
+            Document::builder().name("GreaterThanOrEqual").field("integer", 5).build(),
+            Document::builder().name("Double").field("integer", 4.5).build(),
+            Document::builder().name("StringEquals").field("string", "TestString").build(),
+            Document::builder().name("IsNull").field("test_null", FieldValue::null_value()).build(),
+        ]
+    }
+    fn names(documents: Vec<Document>) -> Vec<String> {
+        documents.iter()
+            .map(|d| d.name().to_string())
+            .collect()
+    }
+    #[test]
+    fn cross_type_ordering() {
+        let ordered = vec![
+            FieldValue::null_value(),
+            FieldValue::from(false),
+            FieldValue::from(true),
+            FieldValue::from(std::f64::NAN),
+            FieldValue::from(-1),
+            FieldValue::from(0.5),
+            FieldValue::from(1),
+            FieldValue::timestamp_value("2019-01-01T00:00:00Z"),
+            FieldValue::from("a"),
+            FieldValue::from("b"),
+            FieldValue::reference_value("a/b"),
+            FieldValue::from((0.0, 0.0)),
+            FieldValue::from(vec![FieldValue::from(1)]),
+            FieldValue::from(vec![FieldValue::from(1), FieldValue::from(2)]),
+        ];
+        for (i, a) in ordered.iter().enumerate() {
+            for (j, b) in ordered.iter().enumerate() {
+                assert_eq!(compare(a, b), i.cmp(&j), "{:?} <> {:?}", a, b);
+            }
+        }
+        assert_eq!(compare(&FieldValue::from(1), &FieldValue::from(1.0)), Ordering::Equal);
+    }
+    #[test]
+    fn bytes_ordering() {
+        let bytes = |b64: &str| FieldValue(Value {
+            bytes_value: Some(b64.to_string()),
+            ..Value::default()
+        });
+        assert_eq!(decode_base64("AAH4"), Some(vec![0x00, 0x01, 0xf8]));
+        assert_eq!(decode_base64("+A=="), Some(vec![0xf8]));
+        // "+A==" (0xf8) sorts before "BA==" (0x04) as text
+        assert_eq!(compare(&bytes("BA=="), &bytes("+A==")), Ordering::Less);
+        assert_eq!(compare(&bytes("/w=="), &bytes("AAA=")), Ordering::Greater);
+        assert_eq!(compare(&bytes("AQ=="), &bytes("AQI=")), Ordering::Less);
+    }
+    #[test]
+    fn nested_field_path() {
+        let mut inner = std::collections::HashMap::new();
+        inner.insert("b.c".to_string(), FieldValue::from(5));
+        let document = Document::builder()
+            .name("Nested")
+            .field("a", inner)
+            .build();
+        assert_eq!(field_value(&document, "a.`b.c`"), Some(FieldValue::from(5)));
+        assert_eq!(field_value(&document, "a.b"), None);
+    }
+    #[test]
+    fn less_than_or_equal() {
+        let filter = field_filter("integer", "LESS_THAN_OR_EQUAL", 5);
+        let results = evaluate(&filter, &[], None, None, 0, 0, test_documents());
+        assert_eq!(names(results),
+                   vec!["LessThan", "Double", "GreaterThanOrEqual", "LessThanOrEqual"]);
+    }
+    #[test]
+    fn comparisons_match_same_type() {
+        let filter = field_filter("string", "GREATER_THAN", 0);
+        assert!(evaluate(&filter, &[], None, None, 0, 0, test_documents()).is_empty());
+    }
+    #[test]
+    fn list_operators() {
+        let list = |values: Vec<FieldValue>| FieldValue::from(values);
+        let filter = field_filter("integer", "IN", list(vec![FieldValue::from(3),
+                                                              FieldValue::from(6)]));
+        // IN is no inequality, so results are ordered by name
+        let results = evaluate(&filter, &[], None, None, 0, 0, test_documents());
+        assert_eq!(names(results), vec!["GreaterThan", "LessThan"]);
+        let filter = field_filter("integer", "NOT_IN", list(vec![FieldValue::from(5),
+                                                                  FieldValue::from(4.5)]));
+        let results = evaluate(&filter, &[], None, None, 0, 0, test_documents());
+        assert_eq!(names(results), vec!["LessThan", "GreaterThan"]);
+        let filter = field_filter("integer", "NOT_EQUAL", 5);
+        let results = evaluate(&filter, &[], None, None, 0, 0, test_documents());
+        assert_eq!(names(results), vec!["LessThan", "Double", "GreaterThan"]);
+        let document = Document::builder()
+            .name("Array")
+            .field("numbers", list(vec![FieldValue::from(1), FieldValue::from(2)]))
+            .build();
+        let filter = field_filter("numbers", "ARRAY_CONTAINS_ANY", list(vec![FieldValue::from(2),
+                                                                              FieldValue::from(7)]));
+        assert!(matches(&filter, &document));
+        let filter = field_filter("numbers", "ARRAY_CONTAINS_ANY", list(vec![FieldValue::from(7)]));
+        assert!(!matches(&filter, &document));
+    }
+    #[test]
+    fn not_null() {
+        let filter = Filter {
+            unary_filter: Some(UnaryFilter {
+                field: Some(FieldReference {
+                    field_path: Some("test_null".to_string()),
+                }),
+                op: Some("IS_NOT_NULL".to_string()),
+            }),
+            ..Filter::default()
+        };
+        assert!(evaluate(&filter, &[], None, None, 0, 0, test_documents()).is_empty());
+    }
+    #[test]
+    fn composite() {
+        let filter = Filter {
+            composite_filter: Some(CompositeFilter {
+                filters: Some(vec![
+                    field_filter("integer", "EQUAL", 6),
+                    field_filter("string", "EQUAL", "TestString"),
+                ]),
+                op: Some("OR".to_string()),
+            }),
+            ..Filter::default()
+        };
+        let results = evaluate(&filter, &[], None, None, 0, 0, test_documents());
+        assert_eq!(names(results), vec!["GreaterThan", "StringEquals"]);
+    }
+    #[test]
+    fn order_limit_skip() {
+        let orders = vec![Order {
+            field: Some(FieldReference {
+                field_path: Some("integer".to_string()),
+            }),
+            direction: Some("DESCENDING".to_string()),
+        }];
+        let results = evaluate(&Filter::default(), &orders, None, None, 2, 1, test_documents());
+        assert_eq!(names(results), vec!["LessThanOrEqual", "GreaterThanOrEqual"]);
+    }
+    #[test]
+    fn cursors() {
+        let orders = vec![Order {
+            field: Some(FieldReference {
+                field_path: Some("integer".to_string()),
+            }),
+            direction: Some("ASCENDING".to_string()),
+        }];
+        let cursor = |value: i32, before: bool| Cursor {
+            values: Some(vec![FieldValue::from(value).0]),
+            before: Some(before),
+        };
+        let filter = Filter::default();
+        // start_at 5
+        let results = evaluate(&filter, &orders, Some(&cursor(5, true)), None, 0, 0, test_documents());
+        assert_eq!(names(results), vec!["GreaterThanOrEqual", "LessThanOrEqual", "GreaterThan"]);
+        // start_after 5
+        let results = evaluate(&filter, &orders, Some(&cursor(5, false)), None, 0, 0, test_documents());
+        assert_eq!(names(results), vec!["GreaterThan"]);
+        // end_before 5
+        let results = evaluate(&filter, &orders, None, Some(&cursor(5, true)), 0, 0, test_documents());
+        assert_eq!(names(results), vec!["LessThan", "Double"]);
+        // start_after 3, end_at 5
+        let results = evaluate(&filter, &orders, Some(&cursor(3, false)), Some(&cursor(5, false)), 0, 0, test_documents());
+        assert_eq!(names(results), vec!["Double", "GreaterThanOrEqual", "LessThanOrEqual"]);
+    }
+}
@@ -1,11 +1,19 @@
 use crate::document::{
     FieldValue,
 };
+use crate::error::{
+    DatabaseError,
+    DatabaseErrorKind,
+};
 
 // Filters are objects which represent simple
-// predicate functions to be used in a query
-pub trait Filter<T: Into<FieldValue> = ()> {
-    fn filter(self, field: &str, op: FilterOp<T>) -> Self;
+// predicate functions to be used in a query.
+// Filters added to a query are combined with AND
+pub trait Filter: Sized {
+    fn filter_expr(self, expr: FilterExpr) -> Self;
+    fn filter<T: Into<FieldValue>>(self, field: &str, op: FilterOp<T>) -> Self {
+        self.filter_expr(FilterExpr::field(field, op))
+    }
     fn and(self, other: Self) -> Self;
     fn or(self, other: Self) -> Self;
 }
@@ -13,11 +21,238 @@ pub trait Filter<T: Into<FieldValue> = ()> {
 #[derive(Clone, Debug)]
 pub enum FilterOp<T: Into<FieldValue> = ()> {
     EQUAL(T),
+    NOT_EQUAL(T),
     LESS_THAN(T),
     LESS_THAN_OR_EQUAL(T),
     GREATER_THAN(T),
     GREATER_THAN_OR_EQUAL(T),
     ARRAY_CONTAINS(T),
+    // the field is equal to any of the values
+    IN(Vec<T>),
+    // the field exists and is equal to none of the values
+    NOT_IN(Vec<T>),
+    // the field is an array containing any of the values
+    ARRAY_CONTAINS_ANY(Vec<T>),
     IS_NULL,
+    IS_NOT_NULL,
     IS_NAN,
+    IS_NOT_NAN,
+}
+
+impl<T: Into<FieldValue>> FilterOp<T> {
+    // the same operator with its operands converted to field values
+    pub fn into_values(self) -> FilterOp<FieldValue> {
+        let list = |values: Vec<T>| values.into_iter()
+                                          .map(|v| v.into())
+                                          .collect();
+        match self {
+            FilterOp::EQUAL(v) => FilterOp::EQUAL(v.into()),
+            FilterOp::NOT_EQUAL(v) => FilterOp::NOT_EQUAL(v.into()),
+            FilterOp::LESS_THAN(v) => FilterOp::LESS_THAN(v.into()),
+            FilterOp::LESS_THAN_OR_EQUAL(v) => FilterOp::LESS_THAN_OR_EQUAL(v.into()),
+            FilterOp::GREATER_THAN(v) => FilterOp::GREATER_THAN(v.into()),
+            FilterOp::GREATER_THAN_OR_EQUAL(v) => FilterOp::GREATER_THAN_OR_EQUAL(v.into()),
+            FilterOp::ARRAY_CONTAINS(v) => FilterOp::ARRAY_CONTAINS(v.into()),
+            FilterOp::IN(values) => FilterOp::IN(list(values)),
+            FilterOp::NOT_IN(values) => FilterOp::NOT_IN(list(values)),
+            FilterOp::ARRAY_CONTAINS_ANY(values) => FilterOp::ARRAY_CONTAINS_ANY(list(values)),
+            FilterOp::IS_NULL => FilterOp::IS_NULL,
+            FilterOp::IS_NOT_NULL => FilterOp::IS_NOT_NULL,
+            FilterOp::IS_NAN => FilterOp::IS_NAN,
+            FilterOp::IS_NOT_NAN => FilterOp::IS_NOT_NAN,
+        }
+    }
+    // the opposite operator, None for the array operators. It does
+    // not match everything this one does not: neither matches
+    // documents without the field, NOT_EQUAL and NOT_IN do not match
+    // null and comparisons only match values of the operand's type
+    pub fn negated(self) -> Option<Self> {
+        Some(match self {
+            FilterOp::EQUAL(v) => FilterOp::NOT_EQUAL(v),
+            FilterOp::NOT_EQUAL(v) => FilterOp::EQUAL(v),
+            FilterOp::LESS_THAN(v) => FilterOp::GREATER_THAN_OR_EQUAL(v),
+            FilterOp::LESS_THAN_OR_EQUAL(v) => FilterOp::GREATER_THAN(v),
+            FilterOp::GREATER_THAN(v) => FilterOp::LESS_THAN_OR_EQUAL(v),
+            FilterOp::GREATER_THAN_OR_EQUAL(v) => FilterOp::LESS_THAN(v),
+            FilterOp::IN(values) => FilterOp::NOT_IN(values),
+            FilterOp::NOT_IN(values) => FilterOp::IN(values),
+            FilterOp::IS_NULL => FilterOp::IS_NOT_NULL,
+            FilterOp::IS_NOT_NULL => FilterOp::IS_NULL,
+            FilterOp::IS_NAN => FilterOp::IS_NOT_NAN,
+            FilterOp::IS_NOT_NAN => FilterOp::IS_NAN,
+            FilterOp::ARRAY_CONTAINS(_) |
+            FilterOp::ARRAY_CONTAINS_ANY(_) => return None,
+        })
+    }
+}
+
+// A filter expression of field filters combined with AND and OR.
+// Operands are stored as FieldValues, so filters on values of
+// different types can be combined
+#[derive(Clone, Debug)]
+pub enum FilterExpr {
+    Field(String, FilterOp<FieldValue>),
+    // an empty AND matches all documents
+    And(Vec<FilterExpr>),
+    Or(Vec<FilterExpr>),
+}
+
+impl Default for FilterExpr {
+    fn default() -> Self {
+        FilterExpr::And(Vec::new())
+    }
+}
+
+impl FilterExpr {
+    pub fn field<T: Into<FieldValue>>(field: &str, op: FilterOp<T>) -> Self {
+        FilterExpr::Field(field.to_string(), op.into_values())
+    }
+    // true if the expression matches all documents
+    pub fn is_empty(&self) -> bool {
+        match self {
+            FilterExpr::And(filters) => filters.is_empty(),
+            _ => false,
+        }
+    }
+    pub fn and(self, other: Self) -> Self {
+        let mut filters = match self {
+            FilterExpr::And(filters) => filters,
+            expr => vec![expr],
+        };
+        match other {
+            FilterExpr::And(others) => filters.extend(others),
+            expr => filters.push(expr),
+        }
+        match filters.len() {
+            1 => filters.pop().unwrap(),
+            _ => FilterExpr::And(filters),
+        }
+    }
+    // an OR with an empty expression matches all documents
+    pub fn or(self, other: Self) -> Self {
+        if self.is_empty() || other.is_empty() {
+            return FilterExpr::default();
+        }
+        let mut filters = match self {
+            FilterExpr::Or(filters) => filters,
+            expr => vec![expr],
+        };
+        match other {
+            FilterExpr::Or(others) => filters.extend(others),
+            expr => filters.push(expr),
+        }
+        FilterExpr::Or(filters)
+    }
+    // the expression with the opposite operators, combined with
+    // AND and OR swapped. This is not a logical NOT, which Firestore
+    // does not have: the result matches no documents without the
+    // field or with null in it for EQUAL and IN, and no values of
+    // another type for comparisons, e.g. LESS_THAN(1) becomes
+    // GREATER_THAN_OR_EQUAL(1), which matches no strings.
+    // Array operators can not be negated
+    pub fn negate_operators(self) -> Result<Self, DatabaseError> {
+        match self {
+            FilterExpr::Field(field, op) => op.clone()
+                .negated()
+                .map(|op| FilterExpr::Field(field.clone(), op))
+                .ok_or_else(|| DatabaseError::new(
+                        DatabaseErrorKind::InvalidArgument,
+                        format!("Can't negate filter {:?} on {}", op, field))),
+            FilterExpr::And(filters) => if filters.is_empty() {
+                    Err(DatabaseError::new(
+                            DatabaseErrorKind::InvalidArgument,
+                            "Can't negate a filter matching all documents"))
+                } else {
+                    filters.into_iter()
+                        .map(|f| f.negate_operators())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map(|filters| filters.into_iter()
+                                              .fold(None, |acc: Option<Self>, f|
+                                                    Some(match acc {
+                                                        Some(acc) => acc.or(f),
+                                                        None => f,
+                                                    }))
+                                              .unwrap())
+                },
+            FilterExpr::Or(filters) => filters.into_iter()
+                .map(|f| f.negate_operators())
+                .collect::<Result<Vec<_>, _>>()
+                .map(|filters| filters.into_iter()
+                                      .fold(FilterExpr::default(), |acc, f| acc.and(f))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        document::Document,
+        firestore::query::FirestoreQuery,
+        query::Query,
+    };
+    fn fields(expr: &FilterExpr) -> Vec<String> {
+        match expr {
+            FilterExpr::Field(field, op) => vec![format!("{} {:?}", field, op)],
+            FilterExpr::And(filters) |
+            FilterExpr::Or(filters) => filters.iter().flat_map(fields).collect(),
+        }
+    }
+    #[test]
+    fn flatten() {
+        let expr = FilterExpr::default()
+            .and(FilterExpr::field("a", FilterOp::EQUAL(1)))
+            .and(FilterExpr::field("b", FilterOp::EQUAL("x"))
+                     .and(FilterExpr::field("c", FilterOp::<()>::IS_NULL)));
+        match &expr {
+            FilterExpr::And(filters) => assert_eq!(filters.len(), 3),
+            _ => panic!("Expected AND: {:?}", expr),
+        }
+        let expr = FilterExpr::field("a", FilterOp::EQUAL(1))
+            .or(FilterExpr::field("a", FilterOp::EQUAL(2)))
+            .or(FilterExpr::field("a", FilterOp::EQUAL(3)));
+        match &expr {
+            FilterExpr::Or(filters) => assert_eq!(filters.len(), 3),
+            _ => panic!("Expected OR: {:?}", expr),
+        }
+    }
+    #[test]
+    fn negate_operators() {
+        let expr = FilterExpr::field("a", FilterOp::LESS_THAN(1))
+            .and(FilterExpr::field("b", FilterOp::IN(vec![1, 2])))
+            .negate_operators()
+            .unwrap();
+        match &expr {
+            FilterExpr::Or(filters) => assert_eq!(filters.len(), 2),
+            _ => panic!("Expected OR: {:?}", expr),
+        }
+        assert_eq!(fields(&expr).len(), 2);
+        assert!(fields(&expr)[0].starts_with("a GREATER_THAN_OR_EQUAL"));
+        assert!(fields(&expr)[1].starts_with("b NOT_IN"));
+        assert!(FilterExpr::field("a", FilterOp::ARRAY_CONTAINS(1)).negate_operators().is_err());
+    }
+    #[test]
+    fn negation_excludes_missing_and_other_types() {
+        let documents = vec![
+            Document::builder().name("Missing").build(),
+            Document::builder().name("Null").field("n", FieldValue::null_value()).build(),
+            Document::builder().name("One").field("n", 1).build(),
+            Document::builder().name("Text").field("n", "a").build(),
+            Document::builder().name("Zero").field("n", 0).build(),
+        ];
+        let names = |expr: FilterExpr| FirestoreQuery::new()
+            .filter_expr(expr)
+            .evaluate(documents.clone())
+            .iter()
+            .map(|d| d.name().to_string())
+            .collect::<Vec<_>>();
+        let less = FilterExpr::field("n", FilterOp::LESS_THAN(1));
+        assert_eq!(names(less.clone()), vec!["Zero"]);
+        assert_eq!(names(less.clone().negate_operators().unwrap()), vec!["One"]);
+        // together they do not match all documents
+        assert_eq!(names(less.clone().or(less.negate_operators().unwrap())),
+                   vec!["One", "Zero"]);
+        let equal = FilterExpr::field("n", FilterOp::EQUAL(1));
+        assert_eq!(names(equal.negate_operators().unwrap()), vec!["Text", "Zero"]);
+    }
 }
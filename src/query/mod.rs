@@ -1,13 +1,18 @@
 pub mod filter;
 pub mod ordering;
+pub mod eval;
+pub mod cursor;
+pub mod paginator;
+pub mod aggregation;
 
 use crate::{
-    error::*,
     database::{
         Database,
+        DatabaseFuture,
     },
     document::{
         Document,
+        FieldValue,
     },
     firestore::query::{
         CollectionSelector,
@@ -15,6 +20,7 @@ use crate::{
 };
 
 use ordering::{ Ordering };
+use cursor::{ Cursor };
 
 pub trait Query<'a, DB: Database<'a>> {
     fn new() -> Self;
@@ -25,6 +31,24 @@ pub trait Query<'a, DB: Database<'a>> {
         self,
         collections: Vec<CollectionSelector>,
         ) -> Self;
+    // query the collections with the id at any depth
+    // below the parent, e.g. the comments of all posts
+    fn collection_group<T: ToString>(
+        self,
+        collection_id: T,
+        ) -> Self
+        where Self: Sized
+    {
+        let mut selector = CollectionSelector::from(collection_id);
+        selector.set_all_descendants(true);
+        self.collections(vec![selector])
+    }
+    // query the collections of a document instead of the
+    // database root, e.g. "posts/abc" for the collections of a post
+    fn under(
+        self,
+        parent_document_path: &str,
+        ) -> Self;
     // define result orders for field
     fn order_by(
         self,
@@ -41,6 +65,42 @@ pub trait Query<'a, DB: Database<'a>> {
         self,
         skip: u32,
         ) -> Self;
+    // only return the given fields of the documents. The fields
+    // the query is ordered by are returned as well, so the
+    // results can be used as cursors, e.g. by a Paginator
+    fn select(
+        self,
+        fields: &[&str],
+        ) -> Self;
+    // start the results at the cursor, including documents at it
+    fn start_at<C: Into<Cursor>>(
+        self,
+        cursor: C,
+        ) -> Self;
+    // start the results after the cursor
+    fn start_after<C: Into<Cursor>>(
+        self,
+        cursor: C,
+        ) -> Self;
+    // end the results at the cursor, including documents at it
+    fn end_at<C: Into<Cursor>>(
+        self,
+        cursor: C,
+        ) -> Self;
+    // end the results before the cursor
+    fn end_before<C: Into<Cursor>>(
+        self,
+        cursor: C,
+        ) -> Self;
+    // the values of a document for the orders of the query,
+    // which are used as cursor to continue after the document
+    fn cursor_values(
+        &self,
+        document: &Document,
+        ) -> Vec<FieldValue>;
+    // identifies which results the query reads regardless of
+    // paging, by its parent, collections, filter and orders
+    fn fingerprint(&self) -> u64;
     // run the query and return the results
-    fn run(self) -> Result<Vec<Document>, DatabaseError>;
+    fn run(self) -> DatabaseFuture<Vec<Document>>;
 }
@@ -0,0 +1,154 @@
+use crate::{
+    database::Database,
+    document::{
+        Document,
+        FieldValue,
+    },
+    error::{
+        DatabaseError,
+        DatabaseErrorKind,
+    },
+    query::Query,
+};
+use serde::{
+    Serialize,
+    Deserialize,
+};
+use std::marker::PhantomData;
+
+// Paginator reads the results of a query in pages of a fixed size.
+// Each page starts after the last document of the previous one,
+// so reading a page costs the same no matter how far it is.
+// The position can be saved as a token and continued later
+pub struct Paginator<'a, DB, Q>
+    where DB: Database<'a>,
+          Q: Query<'a, DB> + Clone
+{
+    query: Q,
+    page_size: u32,
+    // cursor values of the last document read
+    position: Option<Vec<FieldValue>>,
+    done: bool,
+    _database: PhantomData<&'a DB>,
+}
+
+// the position saved in a page token, with the query
+// and page size it can be continued with
+#[derive(Serialize, Deserialize)]
+struct Token {
+    query: u64,
+    page_size: u32,
+    values: Vec<google_firestore::Value>,
+}
+
+fn invalid_token<T: ToString>(message: T) -> DatabaseError {
+    DatabaseError::new(DatabaseErrorKind::InvalidArgument,
+                       format!("Invalid page token: {}", message.to_string()))
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn decode_hex(token: &str) -> Result<Vec<u8>, DatabaseError> {
+    if !token.is_ascii() {
+        return Err(invalid_token("not hexadecimal"));
+    }
+    if token.len() % 2 != 0 {
+        return Err(invalid_token("odd length"));
+    }
+    (0..token.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&token[i..i + 2], 16).map_err(invalid_token))
+        .collect()
+}
+
+impl<'a, DB, Q> Paginator<'a, DB, Q>
+    where DB: Database<'a>,
+          Q: Query<'a, DB> + Clone
+{
+    // the limit of the query is replaced by the page size. Its start
+    // cursor and skip only apply to the first page, later pages
+    // start after the last document of the previous page
+    pub fn new(query: Q, page_size: u32) -> Self {
+        Self {
+            query,
+            page_size,
+            position: None,
+            done: false,
+            _database: PhantomData,
+        }
+    }
+    // continues at the position of a token returned by token(),
+    // which has to be for the same query and page size
+    pub fn from_token(query: Q, page_size: u32, token: &str) -> Result<Self, DatabaseError> {
+        let token: Token = json::from_slice(&decode_hex(token)?)
+            .map_err(invalid_token)?;
+        if token.query != query.fingerprint() || token.page_size != page_size {
+            return Err(invalid_token("it is for a different query or page size"));
+        }
+        Ok(Self {
+            position: if token.values.is_empty() {
+                          None
+                      } else {
+                          Some(token.values.into_iter().map(FieldValue::from).collect())
+                      },
+            ..Self::new(query, page_size)
+        })
+    }
+    // an opaque token for the current position,
+    // None if all pages have been read
+    pub fn token(&self) -> Option<String> {
+        if self.done {
+            return None;
+        }
+        let token = Token {
+            query: self.query.fingerprint(),
+            page_size: self.page_size,
+            values: self.position.iter()
+                .flatten()
+                .map(|v| v.0.clone())
+                .collect(),
+        };
+        json::to_vec(&token).ok().map(|bytes| encode_hex(&bytes))
+    }
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+    // reads the next page. Pages are empty
+    // once all documents have been read
+    pub async fn next_page(&mut self) -> Result<Vec<Document>, DatabaseError> {
+        if self.done {
+            return Ok(Vec::new());
+        }
+        let query = self.query.clone().limit(self.page_size);
+        let query = match &self.position {
+            // the position is already past the skipped documents
+            Some(values) => query.skip(0).start_after(values.clone()),
+            None => query,
+        };
+        let page = query.run().await?;
+        match page.last() {
+            Some(last) if page.len() as u32 == self.page_size =>
+                self.position = Some(self.query.cursor_values(last)),
+            _ => self.done = true,
+        }
+        Ok(page)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn hex() {
+        let bytes = b"[{\"integerValue\":\"5\"}]";
+        assert_eq!(decode_hex(&encode_hex(bytes)).unwrap(), bytes.to_vec());
+        assert!(decode_hex("abc").is_err());
+        assert!(decode_hex("zz").is_err());
+        assert_eq!(decode_hex("äa").unwrap_err().to_string(),
+                   invalid_token("not hexadecimal").to_string());
+    }
+}
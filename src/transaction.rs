@@ -0,0 +1,168 @@
+use crate::{
+    error::DatabaseError,
+    database::Database,
+    document::{
+        Document,
+        FieldPath,
+    },
+    write::{
+        Write,
+        FieldTransform,
+        Precondition,
+        SetOptions,
+    },
+    id,
+};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+use std::thread;
+
+// how often a transaction is attempted before giving up
+const MAX_ATTEMPTS: u32 = 5;
+// the delay before the first retry, doubled with every retry
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+// Transactions read documents consistently and
+// queue writes, which are committed atomically
+// only if the read documents were not changed
+pub trait Transaction<'db> : Sized {
+    type Database: Database<'db, Transaction=Self>;
+
+    fn get_document<A: ToString, B: ToString>(
+        &mut self,
+        collection_id: A,
+        document_id: B,
+    ) -> Result<Document, DatabaseError>;
+    fn run_query(
+        &mut self,
+        query: <Self::Database as Database<'db>>::Query,
+    ) -> Result<Vec<Document>, DatabaseError>;
+    // queue a write to be committed with the transaction
+    fn write(&mut self, write: Write);
+    // a new id for a document, see Database::generate_id
+    fn generate_id(&self) -> String;
+    // the document, named with a generated id if it has no name
+    fn with_id(&self, document: Document) -> Document {
+        id::named_or(document, || self.generate_id())
+    }
+
+    fn create_document<T: ToString>(
+        &mut self,
+        collection_id: T,
+        document: Document,
+    ) {
+        let document = self.with_id(document);
+        self.write(Write::Create {
+            collection_id: collection_id.to_string(),
+            document,
+        })
+    }
+    fn update_document<A: ToString, B: ToString>(
+        &mut self,
+        collection_id: A,
+        document_id: B,
+        document: Document,
+        mask: Option<Vec<FieldPath>>,
+    ) {
+        self.write(Write::Update {
+            collection_id: collection_id.to_string(),
+            document_id: document_id.to_string(),
+            document,
+            mask,
+            transforms: Vec::new(),
+            precondition: Precondition::default(),
+        })
+    }
+    fn set_document<T: ToString>(
+        &mut self,
+        collection_id: T,
+        document: Document,
+        options: SetOptions,
+    ) {
+        let document = self.with_id(document);
+        self.write(Write::Set {
+            collection_id: collection_id.to_string(),
+            document,
+            options,
+        })
+    }
+    fn delete_document<A: ToString, B: ToString>(
+        &mut self,
+        collection_id: A,
+        document_id: B,
+    ) {
+        self.write(Write::Delete {
+            collection_id: collection_id.to_string(),
+            document_id: document_id.to_string(),
+            precondition: None,
+        })
+    }
+    fn transform<A: ToString, B: ToString>(
+        &mut self,
+        collection_id: A,
+        document_id: B,
+        transforms: Vec<FieldTransform>,
+    ) {
+        self.write(Write::Transform {
+            collection_id: collection_id.to_string(),
+            document_id: document_id.to_string(),
+            transforms,
+        })
+    }
+}
+
+// a random delay between half and all of the backoff, so
+// transactions aborted by each other do not retry in lockstep
+fn jittered(backoff: Duration) -> Duration {
+    // RandomState is keyed differently every time it is created
+    let random = RandomState::new().build_hasher().finish();
+    let half = backoff / 2;
+    half + Duration::from_nanos(random % (half.as_nanos() as u64 + 1))
+}
+
+// runs f in a new transaction and commits it. When the transaction
+// is aborted because of contention, it is retried with backoff.
+// This blocks the current thread, so run_transaction
+// implementations run it on a blocking thread
+pub(crate) fn run<'a, DB, T, F>(database: &'a DB, mut f: F) -> Result<T, DatabaseError>
+    where DB: Database<'a>,
+          F: FnMut(&mut DB::Transaction) -> Result<T, DatabaseError>
+{
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        let mut transaction = database.begin_transaction()?;
+        let result = match f(&mut transaction) {
+            Ok(value) => database.commit_transaction(transaction).map(|_| value),
+            Err(e) => {
+                // the error of f is more useful than a failed rollback
+                let _ = database.rollback_transaction(transaction);
+                Err(e)
+            },
+        };
+        match result {
+            Err(ref e) if e.is_aborted() && attempt < MAX_ATTEMPTS => {
+                let delay = jittered(backoff);
+                info!("Transaction aborted ({}), retrying in {:?}", e, delay);
+                thread::sleep(delay);
+                backoff *= 2;
+                attempt += 1;
+            },
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn jitter() {
+        for _ in 0..100 {
+            let delay = jittered(INITIAL_BACKOFF);
+            assert!(delay >= INITIAL_BACKOFF / 2);
+            assert!(delay <= INITIAL_BACKOFF);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use crate::document::{
+    Document,
+    FieldValue,
+    FieldPath,
+};
+use crate::error::{
+    DatabaseError,
+    DatabaseErrorKind,
+};
+
+// Writes describe single changes to documents,
+// which can be committed together atomically
+#[derive(Clone, Debug, PartialEq)]
+pub enum Write {
+    // create a new document, fails if it already exists.
+    // The document needs a name, see Database::with_id.
+    // Creates take no precondition, they always require that
+    // the document does not exist, so no update time can hold
+    Create {
+        collection_id: String,
+        document: Document,
+    },
+    // update a document, see Database::update_document.
+    // The transforms are applied after the update
+    Update {
+        collection_id: String,
+        document_id: String,
+        document: Document,
+        mask: Option<Vec<FieldPath>>,
+        transforms: Vec<FieldTransform>,
+        precondition: Precondition,
+    },
+    Delete {
+        collection_id: String,
+        document_id: String,
+        precondition: Option<Precondition>,
+    },
+    // create or change a document, whether it exists or not
+    Set {
+        collection_id: String,
+        document: Document,
+        options: SetOptions,
+    },
+    // apply transforms to the fields of a document,
+    // creating the document if it does not exist
+    Transform {
+        collection_id: String,
+        document_id: String,
+        transforms: Vec<FieldTransform>,
+    },
+}
+
+impl Write {
+    pub fn collection_id(&self) -> &str {
+        match self {
+            Write::Create { collection_id, .. } |
+            Write::Update { collection_id, .. } |
+            Write::Delete { collection_id, .. } |
+            Write::Set { collection_id, .. } |
+            Write::Transform { collection_id, .. } => collection_id,
+        }
+    }
+    pub fn document_id(&self) -> &str {
+        match self {
+            Write::Create { document, .. } |
+            Write::Set { document, .. } => document.name(),
+            Write::Update { document_id, .. } |
+            Write::Delete { document_id, .. } |
+            Write::Transform { document_id, .. } => document_id,
+        }
+    }
+    pub(crate) fn precondition(&self) -> Option<&Precondition> {
+        match self {
+            Write::Update { precondition, .. } => Some(precondition),
+            Write::Delete { precondition, .. } => precondition.as_ref(),
+            _ => None,
+        }
+    }
+    // fails for writes to documents without a name, which
+    // would write to the path of their collection
+    pub(crate) fn check_document_id(&self) -> Result<(), DatabaseError> {
+        if self.document_id().is_empty() {
+            Err(DatabaseError::new(
+                    DatabaseErrorKind::InvalidArgument,
+                    format!("Can't write a document without a name to {}", self.collection_id())))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// how a set document is combined with an existing document
+#[derive(Clone, Debug, PartialEq)]
+pub enum SetOptions {
+    // replace all fields
+    Overwrite,
+    // change only the fields in the set document,
+    // merging nested maps
+    Merge,
+    // change only these fields, fields missing
+    // in the set document are deleted
+    MergeFields(Vec<FieldPath>),
+}
+
+impl Default for SetOptions {
+    fn default() -> Self {
+        SetOptions::Overwrite
+    }
+}
+
+impl SetOptions {
+    // the fields to change, None for all fields
+    pub(crate) fn mask(&self, document: &Document) -> Option<Vec<FieldPath>> {
+        match self {
+            SetOptions::Overwrite => None,
+            SetOptions::Merge => Some(document.field_paths()),
+            SetOptions::MergeFields(fields) => Some(fields.clone()),
+        }
+    }
+}
+
+// Preconditions make a write fail if the document is not
+// in the expected state. A document which was changed since
+// it was read fails with DatabaseErrorKind::PreconditionFailed
+#[derive(Clone, Debug, PartialEq)]
+pub enum Precondition {
+    // the document must exist (or must not exist), failing
+    // with NotFound (or AlreadyExists) otherwise
+    Exists(bool),
+    // the document must have been last updated at this time
+    UpdateTime(String),
+}
+
+impl Precondition {
+    // the document must not have been changed since it was read.
+    // A document which was not read from the database must not exist
+    pub fn unchanged(document: &Document) -> Self {
+        match document.update_time() {
+            Some(update_time) => Precondition::UpdateTime(update_time),
+            None => Precondition::Exists(false),
+        }
+    }
+}
+
+impl Default for Precondition {
+    // updates only change existing documents by default
+    fn default() -> Self {
+        Precondition::Exists(true)
+    }
+}
+
+// values which are set by the server
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ServerValue {
+    REQUEST_TIME,
+}
+
+impl ToString for ServerValue {
+    fn to_string(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+// FieldTransforms change a field based on its current
+// value, without reading the document first
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldTransform {
+    SetToServerValue(FieldPath, ServerValue),
+    // adds the value to a number, or sets the field
+    // to the value if it is not a number
+    Increment(FieldPath, FieldValue),
+    Maximum(FieldPath, FieldValue),
+    Minimum(FieldPath, FieldValue),
+    // appends the elements which are not in the array yet
+    AppendMissingElements(FieldPath, Vec<FieldValue>),
+    RemoveAllFromArray(FieldPath, Vec<FieldValue>),
+}
+
+impl FieldTransform {
+    pub fn increment<P: Into<FieldPath>, V: Into<FieldValue>>(field: P, value: V) -> Self {
+        FieldTransform::Increment(field.into(), value.into())
+    }
+    // sets the field to the greater of its value and the given value
+    pub fn maximum<P: Into<FieldPath>, V: Into<FieldValue>>(field: P, value: V) -> Self {
+        FieldTransform::Maximum(field.into(), value.into())
+    }
+    // sets the field to the lesser of its value and the given value
+    pub fn minimum<P: Into<FieldPath>, V: Into<FieldValue>>(field: P, value: V) -> Self {
+        FieldTransform::Minimum(field.into(), value.into())
+    }
+    pub fn append_missing_elements<P, V>(field: P, elements: Vec<V>) -> Self
+        where P: Into<FieldPath>,
+              V: Into<FieldValue>
+    {
+        FieldTransform::AppendMissingElements(field.into(),
+                                              elements.into_iter()
+                                                      .map(|e| e.into())
+                                                      .collect())
+    }
+    pub fn remove_all_from_array<P, V>(field: P, elements: Vec<V>) -> Self
+        where P: Into<FieldPath>,
+              V: Into<FieldValue>
+    {
+        FieldTransform::RemoveAllFromArray(field.into(),
+                                           elements.into_iter()
+                                                   .map(|e| e.into())
+                                                   .collect())
+    }
+    // e.g. set_to_server_value("seen", ServerValue::REQUEST_TIME)
+    pub fn set_to_server_value<P: Into<FieldPath>>(field: P, value: ServerValue) -> Self {
+        FieldTransform::SetToServerValue(field.into(), value)
+    }
+    pub fn field_path(&self) -> &FieldPath {
+        match self {
+            FieldTransform::SetToServerValue(path, _) |
+            FieldTransform::Increment(path, _) |
+            FieldTransform::Maximum(path, _) |
+            FieldTransform::Minimum(path, _) |
+            FieldTransform::AppendMissingElements(path, _) |
+            FieldTransform::RemoveAllFromArray(path, _) => path,
+        }
+    }
+}